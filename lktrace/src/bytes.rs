@@ -0,0 +1,81 @@
+//! Little-endian decoding of on-disk structures.
+//!
+//! The guest is riscv64, so every structure in trace data is little
+//! endian with 64-bit longs, whatever the host of analysis is.
+
+/// Cursor which decodes fields in order from a byte slice.
+/// Callers check that the slice holds the whole structure first.
+pub(crate) struct LeReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> LeReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    pub fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        let mut ret = [0u8; N];
+        ret.copy_from_slice(&self.buf[self.pos..self.pos + N]);
+        self.pos += N;
+        ret
+    }
+
+    /// Skip padding.
+    pub fn skip(&mut self, n: usize) -> &mut Self {
+        self.pos += n;
+        self
+    }
+
+    pub fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.bytes())
+    }
+
+    pub fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.bytes())
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.bytes())
+    }
+
+    pub fn i64(&mut self) -> i64 {
+        i64::from_le_bytes(self.bytes())
+    }
+
+    pub fn u64_array<const N: usize>(&mut self) -> [u64; N] {
+        let mut ret = [0u64; N];
+        for v in ret.iter_mut() {
+            *v = self.u64();
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_le_fields() {
+        let buf = [
+            0xcd, 0xab,
+            0x04, 0x03, 0x02, 0x01,
+            0xff, 0xff,
+            0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01,
+            0x9c, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ];
+        let mut r = LeReader::new(&buf);
+        assert_eq!(r.u16(), 0xabcd);
+        assert_eq!(r.u32(), 0x01020304);
+        assert_eq!(r.skip(2).u64(), 0x0102030405060708);
+        assert_eq!(r.i64(), -100);
+    }
+
+    #[test]
+    fn decode_le_array() {
+        let buf = (1u64..=3).flat_map(u64::to_le_bytes).collect::<Vec<_>>();
+        assert_eq!(LeReader::new(&buf).u64_array::<3>(), [1, 2, 3]);
+    }
+}
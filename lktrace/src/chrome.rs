@@ -0,0 +1,152 @@
+//! Export of traces to Chrome trace-event JSON, as loaded by
+//! chrome://tracing and Perfetto.
+//!
+//! Each task (sscratch) is a thread track, grouped into a process by
+//! its address space (satp). A syscall is a slice
+//! from request to reply, and signal delivery is an instant event.
+//! Trace data has no timestamps yet, so the clock is the number of
+//! event in file: one event is one microsecond.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::mem;
+use serde_json::{json, Value};
+use crate::Options;
+use crate::clone::ThreadGroups;
+use crate::error::Result;
+use crate::event::{SeqNames, SigStage, TraceFlow};
+use crate::level1;
+use crate::signal::sig_name;
+
+/// Writer of trace events into a JSON array, one by one.
+struct ChromeWriter {
+    out: BufWriter<File>,
+    names: SeqNames,
+    /// Track ids of address spaces (satp)
+    pids: HashMap<u64, usize>,
+    /// Tracks of tasks, each in the first address space of task.
+    /// A task has several flows in streaming mode
+    tracks: HashMap<u64, (usize, usize)>,
+    /// Address spaces of tasks, fed with their flows
+    groups: ThreadGroups,
+    /// Flows of tasks whose address space isn't known yet, i.e. which
+    /// only have the clone request of their parent so far
+    pending: Vec<(u64, TraceFlow)>,
+    first: bool,
+}
+
+impl ChromeWriter {
+    fn create(path: &str, names: SeqNames) -> Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "{{\"traceEvents\":[")?;
+        Ok(Self {
+            out, names,
+            pids: HashMap::new(),
+            tracks: HashMap::new(),
+            groups: ThreadGroups::new(),
+            pending: vec![],
+            first: true,
+        })
+    }
+
+    fn write(&mut self, value: Value) -> Result<()> {
+        let sep = if self.first { "\n" } else { ",\n" };
+        self.first = false;
+        write!(self.out, "{}{}", sep, value)?;
+        Ok(())
+    }
+
+    /// Track of task in address space satp.
+    fn track(&mut self, tid: u64, satp: u64) -> Result<(usize, usize)> {
+        if let Some(track) = self.tracks.get(&tid) {
+            return Ok(*track);
+        }
+        let pid = match self.pids.get(&satp) {
+            Some(pid) => *pid,
+            None => {
+                let pid = self.pids.len() + 1;
+                self.pids.insert(satp, pid);
+                self.write(json!({
+                    "ph": "M", "name": "process_name", "pid": pid,
+                    "args": { "name": format!("satp {:#x}", satp) },
+                }))?;
+                pid
+            },
+        };
+        let track = self.tracks.len() + 1;
+        self.tracks.insert(tid, (pid, track));
+        self.write(json!({
+            "ph": "M", "name": "thread_name", "pid": pid, "tid": track,
+            "args": { "name": self.names.task(tid) },
+        }))?;
+        Ok((pid, track))
+    }
+
+    /// Write events of task on its own track. A new task starts with a
+    /// copy of its parent's clone request, whose satp is the parent's,
+    /// so its track waits for the first event of its own.
+    fn write_flow(&mut self, tid: u64, flow: TraceFlow) -> Result<()> {
+        self.groups.add(tid, &flow);
+        let space = self.groups.info(tid).and_then(|info| info.spaces.first().copied());
+        let Some(satp) = space else {
+            self.pending.push((tid, flow));
+            return Ok(());
+        };
+        let track = self.track(tid, satp)?;
+        while let Some(idx) = self.pending.iter().position(|(pending, _)| *pending == tid) {
+            let (_, first) = self.pending.remove(idx);
+            self.write_events(track, &first)?;
+        }
+        self.write_events(track, &flow)
+    }
+
+    fn write_events(&mut self, (pid, track): (usize, usize), flow: &TraceFlow) -> Result<()> {
+        for evt in &flow.events {
+            if let SigStage::Enter(signo) = evt.signal {
+                self.write(json!({
+                    "ph": "i", "s": "t", "cat": "signal", "name": sig_name(signo),
+                    "ts": evt.seq, "pid": pid, "tid": track,
+                }))?;
+                continue;
+            }
+            let Some(call) = evt.syscall() else {
+                continue;
+            };
+            // Syscalls without reply, e.g. exit_group, last one event
+            let end = evt.reply_seq.unwrap_or(evt.seq + 1);
+            self.write(json!({
+                "ph": "X", "cat": "syscall", "name": call.name,
+                "ts": evt.seq, "dur": end.saturating_sub(evt.seq),
+                "pid": pid, "tid": track,
+                "args": { "args": call.args.join(", "), "ret": call.ret },
+            }))?;
+        }
+        Ok(())
+    }
+
+    /// Write flows of tasks which never had an event of their own, in
+    /// the address space of their parent's clone request.
+    fn finish(mut self) -> Result<()> {
+        for (tid, flow) in mem::take(&mut self.pending) {
+            // Signal delivery has no head, so no satp
+            let first = flow.events.iter().find(|evt| !matches!(evt.signal, SigStage::Enter(_)));
+            if let Some(first) = first {
+                let track = self.track(tid, first.head.satp)?;
+                self.write_events(track, &flow)?;
+            }
+        }
+        writeln!(self.out, "\n]}}")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Group events of trace data file by task, and write them into output
+/// as Chrome trace-event JSON.
+pub fn export(path: &str, output: &str, opts: &Options) -> Result<()> {
+    let names = SeqNames::new(opts.level);
+    let mut writer = ChromeWriter::create(output, names.clone())?;
+    level1::group(path, opts, &names, |tid, flow| writer.write_flow(tid, flow))?;
+    writer.finish()
+}
@@ -0,0 +1,293 @@
+//! Clone flags, and thread groups of tasks.
+//!
+//! Tasks are keyed by sscratch, one per thread. A task cloned with
+//! CLONE_THREAD joins the thread group of its parent; any other clone
+//! starts a new process. Threads and vfork children share the address
+//! space (satp) of their parent, which changes by execve.
+
+use std::collections::HashMap;
+use crate::event::{SigStage, TraceFlow};
+use crate::signal::sig_name;
+use crate::sysno::*;
+
+/// Mask of signal sent to parent when child exits
+pub const CSIGNAL: u64 = 0x000000ff;
+/// Set if VM shared between processes
+pub const CLONE_VM: u64 = 0x00000100;
+/// Set if fs info shared between processes
+pub const CLONE_FS: u64 = 0x00000200;
+/// Set if open files shared between processes
+pub const CLONE_FILES: u64 = 0x00000400;
+/// Set if signal handlers and blocked signals shared
+pub const CLONE_SIGHAND: u64 = 0x00000800;
+/// Set if a pidfd should be placed in parent
+pub const CLONE_PIDFD: u64 = 0x00001000;
+/// Set if we want to let tracing continue on the child too
+pub const CLONE_PTRACE: u64 = 0x00002000;
+/// Set if the parent wants the child to wake it up on mm_release
+pub const CLONE_VFORK: u64 = 0x00004000;
+/// Set if we want to have the same parent as the cloner
+pub const CLONE_PARENT: u64 = 0x00008000;
+/// Same thread group
+pub const CLONE_THREAD: u64 = 0x00010000;
+/// New mount namespace group
+pub const CLONE_NEWNS: u64 = 0x00020000;
+/// Share system V SEM_UNDO semantics
+pub const CLONE_SYSVSEM: u64 = 0x00040000;
+/// Create a new TLS for the child
+pub const CLONE_SETTLS: u64 = 0x00080000;
+/// Set the TID in the parent
+pub const CLONE_PARENT_SETTID: u64 = 0x00100000;
+/// Clear the TID in the child
+pub const CLONE_CHILD_CLEARTID: u64 = 0x00200000;
+/// Unused, ignored
+pub const CLONE_DETACHED: u64 = 0x00400000;
+/// Set if the tracing process can't force CLONE_PTRACE on this clone
+pub const CLONE_UNTRACED: u64 = 0x00800000;
+/// Set the TID in the child
+pub const CLONE_CHILD_SETTID: u64 = 0x01000000;
+/// New cgroup namespace
+pub const CLONE_NEWCGROUP: u64 = 0x02000000;
+/// New utsname namespace
+pub const CLONE_NEWUTS: u64 = 0x04000000;
+/// New ipc namespace
+pub const CLONE_NEWIPC: u64 = 0x08000000;
+/// New user namespace
+pub const CLONE_NEWUSER: u64 = 0x10000000;
+/// New pid namespace
+pub const CLONE_NEWPID: u64 = 0x20000000;
+/// New network namespace
+pub const CLONE_NEWNET: u64 = 0x40000000;
+/// Clone io context
+pub const CLONE_IO: u64 = 0x80000000;
+/// Clear any signal handler and reset to SIG_DFL (clone3 only)
+pub const CLONE_CLEAR_SIGHAND: u64 = 0x100000000;
+/// Clone into a specific cgroup given the right permissions (clone3 only)
+pub const CLONE_INTO_CGROUP: u64 = 0x200000000;
+
+const CLONE_FLAGS: [(u64, &str); 26] = [
+    (CLONE_VM, "CLONE_VM"),
+    (CLONE_FS, "CLONE_FS"),
+    (CLONE_FILES, "CLONE_FILES"),
+    (CLONE_SIGHAND, "CLONE_SIGHAND"),
+    (CLONE_PIDFD, "CLONE_PIDFD"),
+    (CLONE_PTRACE, "CLONE_PTRACE"),
+    (CLONE_VFORK, "CLONE_VFORK"),
+    (CLONE_PARENT, "CLONE_PARENT"),
+    (CLONE_THREAD, "CLONE_THREAD"),
+    (CLONE_NEWNS, "CLONE_NEWNS"),
+    (CLONE_SYSVSEM, "CLONE_SYSVSEM"),
+    (CLONE_SETTLS, "CLONE_SETTLS"),
+    (CLONE_PARENT_SETTID, "CLONE_PARENT_SETTID"),
+    (CLONE_CHILD_CLEARTID, "CLONE_CHILD_CLEARTID"),
+    (CLONE_DETACHED, "CLONE_DETACHED"),
+    (CLONE_UNTRACED, "CLONE_UNTRACED"),
+    (CLONE_CHILD_SETTID, "CLONE_CHILD_SETTID"),
+    (CLONE_NEWCGROUP, "CLONE_NEWCGROUP"),
+    (CLONE_NEWUTS, "CLONE_NEWUTS"),
+    (CLONE_NEWIPC, "CLONE_NEWIPC"),
+    (CLONE_NEWUSER, "CLONE_NEWUSER"),
+    (CLONE_NEWPID, "CLONE_NEWPID"),
+    (CLONE_NEWNET, "CLONE_NEWNET"),
+    (CLONE_IO, "CLONE_IO"),
+    (CLONE_CLEAR_SIGHAND, "CLONE_CLEAR_SIGHAND"),
+    (CLONE_INTO_CGROUP, "CLONE_INTO_CGROUP"),
+];
+
+/// Generate name of clone flags, e.g. `CLONE_VM|CLONE_VFORK|SIGCHLD`.
+/// The low byte (CSIGNAL) is the signal sent to parent when child
+/// exits, and unknown bits are left in hex.
+pub fn clone_flags_name(flags: u64) -> String {
+    let mut names: Vec<String> = vec![];
+    let mut rest = flags & !CSIGNAL;
+    for (flag, name) in CLONE_FLAGS {
+        if rest & flag != 0 {
+            names.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 {
+        names.push(format!("{:#x}", rest));
+    }
+    let signum = flags & CSIGNAL;
+    if signum != 0 {
+        match sig_name(signum) {
+            name if name == "SIGUNKNOWN" => names.push(format!("{:#x}", signum)),
+            name => names.push(name),
+        }
+    }
+    if names.is_empty() {
+        String::from("0")
+    } else {
+        names.join("|")
+    }
+}
+
+/// What clone with flags creates: thread, vfork child or process.
+pub fn clone_kind(flags: u64) -> &'static str {
+    if flags & CLONE_THREAD != 0 {
+        "thread"
+    } else if flags & CLONE_VFORK != 0 {
+        "vfork"
+    } else {
+        "process"
+    }
+}
+
+/// Parent of task, if its flow starts with the clone request of its
+/// parent, as paired by [`crate::level1::Grouper`]. That event is a
+/// copy of the parent's request, which the parent's flow also has.
+pub fn cloned_by(tid: u64, flow: &TraceFlow) -> Option<u64> {
+    let first = flow.events.first()?;
+    let parent = first.head.sscratch;
+    (flow.flushed == 0 && first.head.ax[7] == SYS_CLONE && parent != tid).then_some(parent)
+}
+
+/// Task as seen by its clone and its address spaces.
+#[derive(Clone, Debug, Default)]
+pub struct CloneInfo {
+    /// Task which cloned this one, and flags of that clone
+    pub parent: Option<(u64, u64)>,
+    /// Address spaces (satp) of task in order
+    pub spaces: Vec<u64>,
+    /// Number of first event of task
+    pub start: u64,
+    /// Satp of parent at clone, until the first event of child
+    clone_satp: Option<u64>,
+    /// Last syscall, as satp changes after execve
+    last_sysno: u64,
+}
+
+/// Thread groups of tasks, fed with flows of tasks.
+///
+/// Flows of a thread may come before those of its parent, so groups
+/// are only complete after all flows are added.
+#[derive(Default)]
+pub struct ThreadGroups {
+    tasks: HashMap<u64, CloneInfo>,
+}
+
+impl ThreadGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add events of task. Address spaces which don't agree with clone
+    /// flags, or change without execve, are warned about.
+    pub fn add(&mut self, tid: u64, flow: &TraceFlow) {
+        let Some(first) = flow.events.first() else {
+            return;
+        };
+        let task = self.tasks.entry(tid).or_insert_with(|| CloneInfo {
+            start: first.seq,
+            ..Default::default()
+        });
+        task.start = task.start.min(first.seq);
+        let mut events = flow.events.iter();
+        // A new task starts with the clone request of its parent, whose
+        // satp is the parent's
+        if let Some(parent) = cloned_by(tid, flow) {
+            task.parent = Some((parent, first.head.ax[0]));
+            task.clone_satp = Some(first.head.satp);
+            task.last_sysno = SYS_CLONE;
+            events.next();
+        }
+        for evt in events {
+            // Signal delivery has no head, so no satp
+            if let SigStage::Enter(_) = evt.signal {
+                continue;
+            }
+            let satp = evt.head.satp;
+            if let (Some(clone_satp), Some((_, flags))) = (task.clone_satp.take(), task.parent) {
+                let shared = satp == clone_satp;
+                if shared != (flags & CLONE_VM != 0) {
+                    warn!("task {:#x} cloned with flags {:#x} but {} address space of its parent",
+                        tid, flags, if shared { "shares" } else { "doesn't share" });
+                }
+            }
+            match task.spaces.last() {
+                Some(last) if *last == satp => (),
+                Some(last) => {
+                    if task.last_sysno != SYS_EXECVE {
+                        warn!("address space of task {:#x} changes from {:#x} to {:#x} without execve",
+                            tid, last, satp);
+                    }
+                    task.spaces.push(satp);
+                },
+                None => task.spaces.push(satp),
+            }
+            task.last_sysno = evt.head.ax[7];
+        }
+    }
+
+    pub fn info(&self, tid: u64) -> Option<&CloneInfo> {
+        self.tasks.get(&tid)
+    }
+
+    /// Leader of thread group of task, i.e. the first task of its
+    /// process in trace.
+    pub fn leader(&self, tid: u64) -> u64 {
+        let mut leader = tid;
+        // Bounded, in case of a damaged trace
+        for _ in 0..=self.tasks.len() {
+            match self.tasks.get(&leader).and_then(|t| t.parent) {
+                Some((parent, flags)) if flags & CLONE_THREAD != 0 => leader = parent,
+                _ => break,
+            }
+        }
+        leader
+    }
+
+    /// Thread groups in order of first event, each with its tasks in
+    /// order of first event. The leader is first, unless it isn't in
+    /// trace.
+    pub fn groups(&self) -> Vec<(u64, Vec<u64>)> {
+        let mut tids = self.tasks.keys().copied().collect::<Vec<_>>();
+        tids.sort_by_key(|tid| (self.tasks[tid].start, *tid));
+        let mut groups: Vec<(u64, Vec<u64>)> = vec![];
+        let mut index = HashMap::new();
+        for tid in tids {
+            let leader = self.leader(tid);
+            let idx = *index.entry(leader).or_insert_with(|| {
+                groups.push((leader, vec![]));
+                groups.len() - 1
+            });
+            groups[idx].1.push(tid);
+        }
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_names() {
+        assert_eq!(clone_flags_name(0), "0");
+        assert_eq!(clone_flags_name(17), "SIGCHLD");
+        assert_eq!(clone_flags_name(CLONE_VM | CLONE_VFORK | 17), "CLONE_VM|CLONE_VFORK|SIGCHLD");
+        // Threads of glibc, which send no signal
+        assert_eq!(
+            clone_flags_name(0x3d0f00),
+            "CLONE_VM|CLONE_FS|CLONE_FILES|CLONE_SIGHAND|CLONE_THREAD|CLONE_SYSVSEM|\
+             CLONE_SETTLS|CLONE_PARENT_SETTID|CLONE_CHILD_CLEARTID",
+        );
+        assert_eq!(clone_flags_name(CLONE_INTO_CGROUP | CLONE_IO), "CLONE_IO|CLONE_INTO_CGROUP");
+        // Unknown bits and signals are left in hex
+        assert_eq!(clone_flags_name(0x400000000 | CLONE_VM | 17), "CLONE_VM|0x400000000|SIGCHLD");
+        assert_eq!(clone_flags_name(0xff), "0xff");
+        assert_eq!(clone_flags_name(0x1000000000000000 | 0x21), "0x1000000000000000|0x21");
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(clone_kind(0x3d0f00), "thread");
+        // Thread wins over vfork, which wins over process
+        assert_eq!(clone_kind(CLONE_THREAD | CLONE_VFORK | CLONE_VM), "thread");
+        assert_eq!(clone_kind(CLONE_VM | CLONE_VFORK | 17), "vfork");
+        assert_eq!(clone_kind(CLONE_VM | 17), "process");
+        assert_eq!(clone_kind(17), "process");
+        assert_eq!(clone_kind(0), "process");
+    }
+}
@@ -1,34 +1,499 @@
-//! Linux errno
+//! Linux errno of asm-generic, as riscv64 uses, with messages as
+//! strerror gives them.
+//!
+//! Errnos above 511 are internal to kernel, and never seen by user, but
+//! may be seen in trace when a syscall is interrupted by signal.
+
+use std::borrow::Cow;
 
 /// Operation not permitted
-pub const EPERM:  i32 = 1;
+pub const EPERM: i32 = 1;
 /// No such file or directory
 pub const ENOENT: i32 = 2;
+/// No such process
+pub const ESRCH: i32 = 3;
+/// Interrupted system call
+pub const EINTR: i32 = 4;
+/// Input/output error
+pub const EIO: i32 = 5;
+/// No such device or address
+pub const ENXIO: i32 = 6;
+/// Argument list too long
+pub const E2BIG: i32 = 7;
+/// Exec format error
+pub const ENOEXEC: i32 = 8;
+/// Bad file descriptor
+pub const EBADF: i32 = 9;
 /// No child processes
 pub const ECHILD: i32 = 10;
+/// Resource temporarily unavailable
+pub const EAGAIN: i32 = 11;
+/// Cannot allocate memory
+pub const ENOMEM: i32 = 12;
+/// Permission denied
+pub const EACCES: i32 = 13;
+/// Bad address
+pub const EFAULT: i32 = 14;
+/// Block device required
+pub const ENOTBLK: i32 = 15;
+/// Device or resource busy
+pub const EBUSY: i32 = 16;
+/// File exists
+pub const EEXIST: i32 = 17;
+/// Invalid cross-device link
+pub const EXDEV: i32 = 18;
+/// No such device
+pub const ENODEV: i32 = 19;
 /// Not a directory
-pub const ENOTDIR:i32 = 20;
+pub const ENOTDIR: i32 = 20;
 /// Is a directory
 pub const EISDIR: i32 = 21;
 /// Invalid argument
 pub const EINVAL: i32 = 22;
-/// Not a typewriter
+/// Too many open files in system
+pub const ENFILE: i32 = 23;
+/// Too many open files
+pub const EMFILE: i32 = 24;
+/// Inappropriate ioctl for device
 pub const ENOTTY: i32 = 25;
+/// Text file busy
+pub const ETXTBSY: i32 = 26;
+/// File too large
+pub const EFBIG: i32 = 27;
+/// No space left on device
+pub const ENOSPC: i32 = 28;
+/// Illegal seek
+pub const ESPIPE: i32 = 29;
+/// Read-only file system
+pub const EROFS: i32 = 30;
+/// Too many links
+pub const EMLINK: i32 = 31;
+/// Broken pipe
+pub const EPIPE: i32 = 32;
+/// Numerical argument out of domain
+pub const EDOM: i32 = 33;
+/// Numerical result out of range
+pub const ERANGE: i32 = 34;
+/// Resource deadlock avoided
+pub const EDEADLK: i32 = 35;
+/// File name too long
+pub const ENAMETOOLONG: i32 = 36;
+/// No locks available
+pub const ENOLCK: i32 = 37;
+/// Function not implemented
+pub const ENOSYS: i32 = 38;
+/// Directory not empty
+pub const ENOTEMPTY: i32 = 39;
+/// Too many levels of symbolic links
+pub const ELOOP: i32 = 40;
+/// No message of desired type
+pub const ENOMSG: i32 = 42;
+/// Identifier removed
+pub const EIDRM: i32 = 43;
+/// Channel number out of range
+pub const ECHRNG: i32 = 44;
+/// Level 2 not synchronized
+pub const EL2NSYNC: i32 = 45;
+/// Level 3 halted
+pub const EL3HLT: i32 = 46;
+/// Level 3 reset
+pub const EL3RST: i32 = 47;
+/// Link number out of range
+pub const ELNRNG: i32 = 48;
+/// Protocol driver not attached
+pub const EUNATCH: i32 = 49;
+/// No CSI structure available
+pub const ENOCSI: i32 = 50;
+/// Level 2 halted
+pub const EL2HLT: i32 = 51;
+/// Invalid exchange
+pub const EBADE: i32 = 52;
+/// Invalid request descriptor
+pub const EBADR: i32 = 53;
+/// Exchange full
+pub const EXFULL: i32 = 54;
+/// No anode
+pub const ENOANO: i32 = 55;
+/// Invalid request code
+pub const EBADRQC: i32 = 56;
+/// Invalid slot
+pub const EBADSLT: i32 = 57;
+/// Bad font file format
+pub const EBFONT: i32 = 59;
+/// Device not a stream
+pub const ENOSTR: i32 = 60;
+/// No data available
+pub const ENODATA: i32 = 61;
+/// Timer expired
+pub const ETIME: i32 = 62;
+/// Out of streams resources
+pub const ENOSR: i32 = 63;
+/// Machine is not on the network
+pub const ENONET: i32 = 64;
+/// Package not installed
+pub const ENOPKG: i32 = 65;
+/// Object is remote
+pub const EREMOTE: i32 = 66;
+/// Link has been severed
+pub const ENOLINK: i32 = 67;
+/// Advertise error
+pub const EADV: i32 = 68;
+/// Srmount error
+pub const ESRMNT: i32 = 69;
+/// Communication error on send
+pub const ECOMM: i32 = 70;
+/// Protocol error
+pub const EPROTO: i32 = 71;
+/// Multihop attempted
+pub const EMULTIHOP: i32 = 72;
+/// RFS specific error
+pub const EDOTDOT: i32 = 73;
+/// Bad message
+pub const EBADMSG: i32 = 74;
+/// Value too large for defined data type
+pub const EOVERFLOW: i32 = 75;
+/// Name not unique on network
+pub const ENOTUNIQ: i32 = 76;
+/// File descriptor in bad state
+pub const EBADFD: i32 = 77;
+/// Remote address changed
+pub const EREMCHG: i32 = 78;
+/// Can not access a needed shared library
+pub const ELIBACC: i32 = 79;
+/// Accessing a corrupted shared library
+pub const ELIBBAD: i32 = 80;
+/// .lib section in a.out corrupted
+pub const ELIBSCN: i32 = 81;
+/// Attempting to link in too many shared libraries
+pub const ELIBMAX: i32 = 82;
+/// Cannot exec a shared library directly
+pub const ELIBEXEC: i32 = 83;
+/// Invalid or incomplete multibyte or wide character
+pub const EILSEQ: i32 = 84;
+/// Interrupted system call should be restarted
+pub const ERESTART: i32 = 85;
+/// Streams pipe error
+pub const ESTRPIPE: i32 = 86;
+/// Too many users
+pub const EUSERS: i32 = 87;
+/// Socket operation on non-socket
+pub const ENOTSOCK: i32 = 88;
+/// Destination address required
+pub const EDESTADDRREQ: i32 = 89;
+/// Message too long
+pub const EMSGSIZE: i32 = 90;
+/// Protocol wrong type for socket
+pub const EPROTOTYPE: i32 = 91;
+/// Protocol not available
+pub const ENOPROTOOPT: i32 = 92;
+/// Protocol not supported
+pub const EPROTONOSUPPORT: i32 = 93;
+/// Socket type not supported
+pub const ESOCKTNOSUPPORT: i32 = 94;
+/// Operation not supported
+pub const EOPNOTSUPP: i32 = 95;
+/// Protocol family not supported
+pub const EPFNOSUPPORT: i32 = 96;
+/// Address family not supported by protocol
+pub const EAFNOSUPPORT: i32 = 97;
+/// Address already in use
+pub const EADDRINUSE: i32 = 98;
+/// Cannot assign requested address
+pub const EADDRNOTAVAIL: i32 = 99;
+/// Network is down
+pub const ENETDOWN: i32 = 100;
+/// Network is unreachable
+pub const ENETUNREACH: i32 = 101;
+/// Network dropped connection on reset
+pub const ENETRESET: i32 = 102;
+/// Software caused connection abort
+pub const ECONNABORTED: i32 = 103;
+/// Connection reset by peer
+pub const ECONNRESET: i32 = 104;
+/// No buffer space available
+pub const ENOBUFS: i32 = 105;
+/// Transport endpoint is already connected
+pub const EISCONN: i32 = 106;
+/// Transport endpoint is not connected
+pub const ENOTCONN: i32 = 107;
+/// Cannot send after transport endpoint shutdown
+pub const ESHUTDOWN: i32 = 108;
+/// Too many references: cannot splice
+pub const ETOOMANYREFS: i32 = 109;
+/// Connection timed out
+pub const ETIMEDOUT: i32 = 110;
+/// Connection refused
+pub const ECONNREFUSED: i32 = 111;
+/// Host is down
+pub const EHOSTDOWN: i32 = 112;
+/// No route to host
+pub const EHOSTUNREACH: i32 = 113;
+/// Operation already in progress
+pub const EALREADY: i32 = 114;
+/// Operation now in progress
+pub const EINPROGRESS: i32 = 115;
+/// Stale file handle
+pub const ESTALE: i32 = 116;
+/// Structure needs cleaning
+pub const EUCLEAN: i32 = 117;
+/// Not a XENIX named type file
+pub const ENOTNAM: i32 = 118;
+/// No XENIX semaphores available
+pub const ENAVAIL: i32 = 119;
+/// Is a named type file
+pub const EISNAM: i32 = 120;
+/// Remote I/O error
+pub const EREMOTEIO: i32 = 121;
+/// Disk quota exceeded
+pub const EDQUOT: i32 = 122;
+/// No medium found
+pub const ENOMEDIUM: i32 = 123;
+/// Wrong medium type
+pub const EMEDIUMTYPE: i32 = 124;
+/// Operation canceled
+pub const ECANCELED: i32 = 125;
+/// Required key not available
+pub const ENOKEY: i32 = 126;
+/// Key has expired
+pub const EKEYEXPIRED: i32 = 127;
+/// Key has been revoked
+pub const EKEYREVOKED: i32 = 128;
+/// Key was rejected by service
+pub const EKEYREJECTED: i32 = 129;
+/// Owner died
+pub const EOWNERDEAD: i32 = 130;
+/// State not recoverable
+pub const ENOTRECOVERABLE: i32 = 131;
+/// Operation not possible due to RF-kill
+pub const ERFKILL: i32 = 132;
+/// Memory page has hardware error
+pub const EHWPOISON: i32 = 133;
+
+// Kernel internal, from include/linux/errno.h
+/// To be restarted if SA_RESTART is set
+pub const ERESTARTSYS: i32 = 512;
+/// To be restarted
+pub const ERESTARTNOINTR: i32 = 513;
+/// To be restarted if no handler
+pub const ERESTARTNOHAND: i32 = 514;
+/// No ioctl command
+pub const ENOIOCTLCMD: i32 = 515;
+/// Interrupted by signal
+pub const ERESTART_RESTARTBLOCK: i32 = 516;
+
+/// Same as EAGAIN
+pub const EWOULDBLOCK: i32 = EAGAIN;
+/// Same as EDEADLK
+pub const EDEADLOCK: i32 = EDEADLK;
+/// Same as EOPNOTSUPP
+pub const ENOTSUP: i32 = EOPNOTSUPP;
+
+/// Largest errno. Syscalls return errors as -MAX_ERRNO..=-1, and any
+/// other value, even a negative one, is a success.
+pub const MAX_ERRNO: i64 = 4095;
+
+/// Result of syscall is an error.
+pub fn is_error(result: i64) -> bool {
+    (-MAX_ERRNO..0).contains(&result)
+}
+
+/// Errnos with names and messages, ordered by errno.
+const ERRNOS: [(i32, &str, &str); 136] = [
+    (EPERM, "EPERM", "Operation not permitted"),
+    (ENOENT, "ENOENT", "No such file or directory"),
+    (ESRCH, "ESRCH", "No such process"),
+    (EINTR, "EINTR", "Interrupted system call"),
+    (EIO, "EIO", "Input/output error"),
+    (ENXIO, "ENXIO", "No such device or address"),
+    (E2BIG, "E2BIG", "Argument list too long"),
+    (ENOEXEC, "ENOEXEC", "Exec format error"),
+    (EBADF, "EBADF", "Bad file descriptor"),
+    (ECHILD, "ECHILD", "No child processes"),
+    (EAGAIN, "EAGAIN", "Resource temporarily unavailable"),
+    (ENOMEM, "ENOMEM", "Cannot allocate memory"),
+    (EACCES, "EACCES", "Permission denied"),
+    (EFAULT, "EFAULT", "Bad address"),
+    (ENOTBLK, "ENOTBLK", "Block device required"),
+    (EBUSY, "EBUSY", "Device or resource busy"),
+    (EEXIST, "EEXIST", "File exists"),
+    (EXDEV, "EXDEV", "Invalid cross-device link"),
+    (ENODEV, "ENODEV", "No such device"),
+    (ENOTDIR, "ENOTDIR", "Not a directory"),
+    (EISDIR, "EISDIR", "Is a directory"),
+    (EINVAL, "EINVAL", "Invalid argument"),
+    (ENFILE, "ENFILE", "Too many open files in system"),
+    (EMFILE, "EMFILE", "Too many open files"),
+    (ENOTTY, "ENOTTY", "Inappropriate ioctl for device"),
+    (ETXTBSY, "ETXTBSY", "Text file busy"),
+    (EFBIG, "EFBIG", "File too large"),
+    (ENOSPC, "ENOSPC", "No space left on device"),
+    (ESPIPE, "ESPIPE", "Illegal seek"),
+    (EROFS, "EROFS", "Read-only file system"),
+    (EMLINK, "EMLINK", "Too many links"),
+    (EPIPE, "EPIPE", "Broken pipe"),
+    (EDOM, "EDOM", "Numerical argument out of domain"),
+    (ERANGE, "ERANGE", "Numerical result out of range"),
+    (EDEADLK, "EDEADLK", "Resource deadlock avoided"),
+    (ENAMETOOLONG, "ENAMETOOLONG", "File name too long"),
+    (ENOLCK, "ENOLCK", "No locks available"),
+    (ENOSYS, "ENOSYS", "Function not implemented"),
+    (ENOTEMPTY, "ENOTEMPTY", "Directory not empty"),
+    (ELOOP, "ELOOP", "Too many levels of symbolic links"),
+    (ENOMSG, "ENOMSG", "No message of desired type"),
+    (EIDRM, "EIDRM", "Identifier removed"),
+    (ECHRNG, "ECHRNG", "Channel number out of range"),
+    (EL2NSYNC, "EL2NSYNC", "Level 2 not synchronized"),
+    (EL3HLT, "EL3HLT", "Level 3 halted"),
+    (EL3RST, "EL3RST", "Level 3 reset"),
+    (ELNRNG, "ELNRNG", "Link number out of range"),
+    (EUNATCH, "EUNATCH", "Protocol driver not attached"),
+    (ENOCSI, "ENOCSI", "No CSI structure available"),
+    (EL2HLT, "EL2HLT", "Level 2 halted"),
+    (EBADE, "EBADE", "Invalid exchange"),
+    (EBADR, "EBADR", "Invalid request descriptor"),
+    (EXFULL, "EXFULL", "Exchange full"),
+    (ENOANO, "ENOANO", "No anode"),
+    (EBADRQC, "EBADRQC", "Invalid request code"),
+    (EBADSLT, "EBADSLT", "Invalid slot"),
+    (EBFONT, "EBFONT", "Bad font file format"),
+    (ENOSTR, "ENOSTR", "Device not a stream"),
+    (ENODATA, "ENODATA", "No data available"),
+    (ETIME, "ETIME", "Timer expired"),
+    (ENOSR, "ENOSR", "Out of streams resources"),
+    (ENONET, "ENONET", "Machine is not on the network"),
+    (ENOPKG, "ENOPKG", "Package not installed"),
+    (EREMOTE, "EREMOTE", "Object is remote"),
+    (ENOLINK, "ENOLINK", "Link has been severed"),
+    (EADV, "EADV", "Advertise error"),
+    (ESRMNT, "ESRMNT", "Srmount error"),
+    (ECOMM, "ECOMM", "Communication error on send"),
+    (EPROTO, "EPROTO", "Protocol error"),
+    (EMULTIHOP, "EMULTIHOP", "Multihop attempted"),
+    (EDOTDOT, "EDOTDOT", "RFS specific error"),
+    (EBADMSG, "EBADMSG", "Bad message"),
+    (EOVERFLOW, "EOVERFLOW", "Value too large for defined data type"),
+    (ENOTUNIQ, "ENOTUNIQ", "Name not unique on network"),
+    (EBADFD, "EBADFD", "File descriptor in bad state"),
+    (EREMCHG, "EREMCHG", "Remote address changed"),
+    (ELIBACC, "ELIBACC", "Can not access a needed shared library"),
+    (ELIBBAD, "ELIBBAD", "Accessing a corrupted shared library"),
+    (ELIBSCN, "ELIBSCN", ".lib section in a.out corrupted"),
+    (ELIBMAX, "ELIBMAX", "Attempting to link in too many shared libraries"),
+    (ELIBEXEC, "ELIBEXEC", "Cannot exec a shared library directly"),
+    (EILSEQ, "EILSEQ", "Invalid or incomplete multibyte or wide character"),
+    (ERESTART, "ERESTART", "Interrupted system call should be restarted"),
+    (ESTRPIPE, "ESTRPIPE", "Streams pipe error"),
+    (EUSERS, "EUSERS", "Too many users"),
+    (ENOTSOCK, "ENOTSOCK", "Socket operation on non-socket"),
+    (EDESTADDRREQ, "EDESTADDRREQ", "Destination address required"),
+    (EMSGSIZE, "EMSGSIZE", "Message too long"),
+    (EPROTOTYPE, "EPROTOTYPE", "Protocol wrong type for socket"),
+    (ENOPROTOOPT, "ENOPROTOOPT", "Protocol not available"),
+    (EPROTONOSUPPORT, "EPROTONOSUPPORT", "Protocol not supported"),
+    (ESOCKTNOSUPPORT, "ESOCKTNOSUPPORT", "Socket type not supported"),
+    (EOPNOTSUPP, "EOPNOTSUPP", "Operation not supported"),
+    (EPFNOSUPPORT, "EPFNOSUPPORT", "Protocol family not supported"),
+    (EAFNOSUPPORT, "EAFNOSUPPORT", "Address family not supported by protocol"),
+    (EADDRINUSE, "EADDRINUSE", "Address already in use"),
+    (EADDRNOTAVAIL, "EADDRNOTAVAIL", "Cannot assign requested address"),
+    (ENETDOWN, "ENETDOWN", "Network is down"),
+    (ENETUNREACH, "ENETUNREACH", "Network is unreachable"),
+    (ENETRESET, "ENETRESET", "Network dropped connection on reset"),
+    (ECONNABORTED, "ECONNABORTED", "Software caused connection abort"),
+    (ECONNRESET, "ECONNRESET", "Connection reset by peer"),
+    (ENOBUFS, "ENOBUFS", "No buffer space available"),
+    (EISCONN, "EISCONN", "Transport endpoint is already connected"),
+    (ENOTCONN, "ENOTCONN", "Transport endpoint is not connected"),
+    (ESHUTDOWN, "ESHUTDOWN", "Cannot send after transport endpoint shutdown"),
+    (ETOOMANYREFS, "ETOOMANYREFS", "Too many references: cannot splice"),
+    (ETIMEDOUT, "ETIMEDOUT", "Connection timed out"),
+    (ECONNREFUSED, "ECONNREFUSED", "Connection refused"),
+    (EHOSTDOWN, "EHOSTDOWN", "Host is down"),
+    (EHOSTUNREACH, "EHOSTUNREACH", "No route to host"),
+    (EALREADY, "EALREADY", "Operation already in progress"),
+    (EINPROGRESS, "EINPROGRESS", "Operation now in progress"),
+    (ESTALE, "ESTALE", "Stale file handle"),
+    (EUCLEAN, "EUCLEAN", "Structure needs cleaning"),
+    (ENOTNAM, "ENOTNAM", "Not a XENIX named type file"),
+    (ENAVAIL, "ENAVAIL", "No XENIX semaphores available"),
+    (EISNAM, "EISNAM", "Is a named type file"),
+    (EREMOTEIO, "EREMOTEIO", "Remote I/O error"),
+    (EDQUOT, "EDQUOT", "Disk quota exceeded"),
+    (ENOMEDIUM, "ENOMEDIUM", "No medium found"),
+    (EMEDIUMTYPE, "EMEDIUMTYPE", "Wrong medium type"),
+    (ECANCELED, "ECANCELED", "Operation canceled"),
+    (ENOKEY, "ENOKEY", "Required key not available"),
+    (EKEYEXPIRED, "EKEYEXPIRED", "Key has expired"),
+    (EKEYREVOKED, "EKEYREVOKED", "Key has been revoked"),
+    (EKEYREJECTED, "EKEYREJECTED", "Key was rejected by service"),
+    (EOWNERDEAD, "EOWNERDEAD", "Owner died"),
+    (ENOTRECOVERABLE, "ENOTRECOVERABLE", "State not recoverable"),
+    (ERFKILL, "ERFKILL", "Operation not possible due to RF-kill"),
+    (EHWPOISON, "EHWPOISON", "Memory page has hardware error"),
+    (ERESTARTSYS, "ERESTARTSYS", "To be restarted if SA_RESTART is set"),
+    (ERESTARTNOINTR, "ERESTARTNOINTR", "To be restarted"),
+    (ERESTARTNOHAND, "ERESTARTNOHAND", "To be restarted if no handler"),
+    (ENOIOCTLCMD, "ENOIOCTLCMD", "No ioctl command"),
+    (ERESTART_RESTARTBLOCK, "ERESTART_RESTARTBLOCK", "Interrupted by signal"),
+];
+
+fn lookup(errno: i128) -> Option<&'static (i32, &'static str, &'static str)> {
+    let errno = i32::try_from(errno).ok()?;
+    ERRNOS.binary_search_by_key(&errno, |e| e.0).ok().map(|i| &ERRNOS[i])
+}
+
+/// Errno by its name, e.g. 2 for ENOENT, if it's known.
+pub fn errno_no(name: &str) -> Option<i32> {
+    ERRNOS.iter().find(|e| e.1 == name).map(|e| e.0)
+}
+
+/// Errno of a result, i.e. the result negated, which doesn't overflow
+/// even for i64::MIN.
+fn errno_of(err: i64) -> i128 {
+    -i128::from(err)
+}
+
+/// Name of errno of a negative result, e.g. ENOENT for -2, or `E<n>`
+/// if it's unknown. Zero is OK.
+pub fn errno_name(err: i64) -> Cow<'static, str> {
+    let errno = errno_of(err);
+    match lookup(errno) {
+        _ if errno == 0 => Cow::Borrowed("OK"),
+        Some((_, name, _)) => Cow::Borrowed(name),
+        None => Cow::Owned(format!("E{}", errno)),
+    }
+}
+
+/// Message of errno as strerror gives it, e.g. for errors in strace
+/// format.
+pub fn errno_msg(err: i64) -> Cow<'static, str> {
+    let errno = errno_of(err);
+    match lookup(errno) {
+        _ if errno == 0 => Cow::Borrowed("Success"),
+        Some((_, _, msg)) => Cow::Borrowed(msg),
+        None => Cow::Owned(format!("Unknown error {}", errno)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errnos_sorted() {
+        assert!(ERRNOS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
 
-pub fn errno_name(err: i64) -> &'static str {
-    let err = err as i32;
-    match -err {
-        0 => "OK",
-        EPERM => "EPERM",
-        ENOENT => "ENOENT",
-        ECHILD => "ECHILD",
-        ENOTDIR => "ENOTDIR",
-        EISDIR => "EISDIR",
-        EINVAL => "EINVAL",
-        ENOTTY => "ENOTTY",
-        _ => {
-            println!("Unknown errno: {}", -err);
-            "Unknown errno"
-        },
+    #[test]
+    fn names_of_results() {
+        assert_eq!(errno_name(0), "OK");
+        assert_eq!(errno_name(-2), "ENOENT");
+        assert_eq!(errno_msg(-2), "No such file or directory");
+        assert_eq!(errno_name(-5000), "E5000");
+        assert_eq!(errno_name(i64::MIN), "E9223372036854775808");
+        assert_eq!(errno_msg(i64::MIN), "Unknown error 9223372036854775808");
+        assert_eq!(errno_name(i64::from(i32::MIN)), "E2147483648");
+        assert_eq!(errno_no("ENOENT"), Some(2));
+        assert_eq!(errno_no("ENOPE"), None);
     }
 }
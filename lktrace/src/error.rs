@@ -0,0 +1,105 @@
+//! Errors of parsing and analysing trace data.
+
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Error of trace data. Offset is the position in data file of the
+/// event (or payload) that caused the error.
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    /// Error of SQLite database which results are written into.
+    #[cfg(feature = "sqlite")]
+    Sqlite(rusqlite::Error),
+    /// Event head doesn't start with LK_MAGIC.
+    BadMagic { offset: u64, magic: u16 },
+    /// Head size isn't TE_SIZE, or total size is less than head size.
+    BadHeadSize { offset: u64, headsize: u16, totalsize: u32 },
+    /// Direction is neither IN nor OUT.
+    BadInout { offset: u64, inout: u64 },
+    /// Trap cause isn't user ecall.
+    BadCause { offset: u64, cause: u64 },
+    /// Syscall number is out of range.
+    BadSysno { offset: u64, sysno: u64 },
+    /// Data file ends in the middle of an event or payload.
+    Truncated { offset: u64, need: usize, left: usize },
+    /// Payload head doesn't start with LK_TRACE_PAYLOAD_MAGIC.
+    BadPayloadMagic { offset: u64, magic: u16 },
+    /// Payload goes beyond the end of its event (totalsize).
+    PayloadOverrun { offset: u64, size: usize, left: usize },
+    /// Payload refers to an argument that doesn't exist.
+    UnknownPayload { offset: u64, index: usize },
+    /// Index file is damaged or of another version.
+    BadIndex { path: String, reason: &'static str },
+    /// Reply doesn't match any pending request of the task.
+    Unmatched { offset: u64, tid: u64, sysno: u64, reason: &'static str },
+}
+
+pub type Result<T> = std::result::Result<T, TraceError>;
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "{}", e),
+            #[cfg(feature = "sqlite")]
+            TraceError::Sqlite(e) => write!(f, "sqlite: {}", e),
+            TraceError::BadMagic { offset, magic } => {
+                write!(f, "bad magic {:#x} at offset {:#x}", magic, offset)
+            },
+            TraceError::BadHeadSize { offset, headsize, totalsize } => {
+                write!(f, "bad head size {} (total {}) at offset {:#x}", headsize, totalsize, offset)
+            },
+            TraceError::BadInout { offset, inout } => {
+                write!(f, "bad inout {:#x} at offset {:#x}", inout, offset)
+            },
+            TraceError::BadCause { offset, cause } => {
+                write!(f, "bad cause {:#x} at offset {:#x}", cause, offset)
+            },
+            TraceError::BadSysno { offset, sysno } => {
+                write!(f, "bad syscall number {} at offset {:#x}", sysno, offset)
+            },
+            TraceError::Truncated { offset, need, left } => {
+                write!(f, "truncated at offset {:#x}: need {} bytes, only {} left", offset, need, left)
+            },
+            TraceError::BadPayloadMagic { offset, magic } => {
+                write!(f, "bad payload magic {:#x} at offset {:#x}", magic, offset)
+            },
+            TraceError::PayloadOverrun { offset, size, left } => {
+                write!(f, "payload at offset {:#x} overruns its event: {} bytes, only {} left", offset, size, left)
+            },
+            TraceError::UnknownPayload { offset, index } => {
+                write!(f, "unknown payload index {} at offset {:#x}", index, offset)
+            },
+            TraceError::BadIndex { path, reason } => {
+                write!(f, "bad index {}: {}", path, reason)
+            },
+            TraceError::Unmatched { offset, tid, sysno, reason } => {
+                write!(f, "unmatched syscall {} of task {:#x} at offset {:#x}: {}", sysno, tid, offset, reason)
+            },
+        }
+    }
+}
+
+impl std::error::Error for TraceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TraceError::Io(e) => Some(e),
+            #[cfg(feature = "sqlite")]
+            TraceError::Sqlite(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for TraceError {
+    fn from(e: io::Error) -> Self {
+        TraceError::Io(e)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl From<rusqlite::Error> for TraceError {
+    fn from(e: rusqlite::Error) -> Self {
+        TraceError::Sqlite(e)
+    }
+}
@@ -1,33 +1,36 @@
-//! Trace event.
+//! Trace event: on-disk layout, parsing and formatting.
 
 use std::fs::File;
-use std::io::BufReader;
-use crate::errno::errno_name;
+use std::io::{BufReader, SeekFrom};
+use crate::clone::clone_flags_name;
+use crate::errno::{errno_name, is_error};
+use crate::fcntl::{access_mode_name, at_flags_name, mode_name, open_flags_name, open_has_mode, AT_FDCWD};
 use crate::mmap::{map_name, prot_name};
+use crate::signature::{signature, Arg, FlagsKind, Param, Ret, StructKind};
 use crate::sysno::*;
 use crate::signal::{SigAction, sig_name};
 use std::ffi::CStr;
-use std::fmt::{Display, Formatter};
-use std::mem;
+use std::fmt::{Display, Formatter, Write as _};
 use crate::signal::{SIG_BLOCK, SIG_UNBLOCK, SIG_SETMASK};
-use crate::payload::parse_payloads;
+use crate::payload::{parse_payloads, PayloadData};
+use crate::parallel::Decoded;
+use crate::Options;
+use crate::index::{Selection, TraceIndex};
+use std::collections::VecDeque;
+use crate::bytes::LeReader;
+use crate::error::{Result, TraceError};
 use std::io::prelude::*;
-use std::io::Result;
 use std::collections::HashMap;
-use std::sync::Mutex;
-use once_cell::unsync::Lazy;
+use std::sync::{Arc, Mutex};
 
 pub const LK_MAGIC: u16 = 0xABCD;
-pub const TE_SIZE: usize = mem::size_of::<TraceHead>();
+/// Size of TraceHead on disk
+pub const TE_SIZE: usize = 200;
 
 pub const USER_ECALL: u64 = 8;
 
-const AT_FDCWD: u64 = -100i64 as u64;
-
-static TID_MAP: Mutex<Lazy<HashMap<i64, String>>> = Mutex::new(Lazy::new(|| HashMap::new()));
-
+/// Head of each event, written by qemu as `trace_event_t`.
 #[derive(Clone, Debug, Default)]
-#[repr(C)]
 pub struct TraceHead {
     pub magic: u16,
     /// TraceHead size
@@ -48,13 +51,16 @@ pub struct TraceHead {
     pub sscratch: u64,
 }
 
+/// Extra data of an event, e.g. a path string or a struct copied
+/// from user memory. Index is the number of related argument.
 #[derive(Clone, Debug, Default)]
 pub struct TracePayload {
     pub inout: u64,
     pub index: usize,
-    pub data: Vec<u8>,
+    pub data: PayloadData,
 }
 
+/// Stage of signal delivery attached to an event.
 #[derive(Clone, Debug, Default)]
 pub enum SigStage {
     #[default]
@@ -63,19 +69,41 @@ pub enum SigStage {
     Exit(u64),
 }
 
+/// A syscall event. After grouping, request and reply are merged,
+/// and `result` holds the return value.
 #[derive(Clone, Debug, Default)]
 pub struct TraceEvent {
     pub head: TraceHead,
+    /// Offset of event in data file
+    pub offset: u64,
+    /// Number of event in file order from 0, which is the clock of
+    /// trace until events have timestamps
+    pub seq: u64,
+    /// Number of reply merged into this request, if any
+    pub reply_seq: Option<u64>,
     pub result: i64,
     pub payloads: Vec<TracePayload>,
     pub signal: SigStage,
+    /// Paths of fd arguments by index, as the fd table of task has
+    /// them at request, see [`crate::fd::FdTables`]
+    pub fd_paths: Vec<(usize, String)>,
+    /// Seq-names of ids of analysis, used at level 2
+    pub names: SeqNames,
     pub raw_fmt: bool,
     pub level: usize,
 }
 
+/// Events of one task in order.
+#[derive(Default)]
 pub struct TraceFlow {
     pub events: Vec<TraceEvent>,
     pub signal_stack: Vec<TraceEvent>,
+    /// Number of events already flushed in streaming mode,
+    /// i.e. index of events[0] in the whole flow
+    pub flushed: usize,
+    /// Pid (or tid) of task given by clone, gettid or getpid, if it's
+    /// known by then
+    pub pid: Option<i64>,
 }
 
 impl TraceFlow {
@@ -83,253 +111,220 @@ impl TraceFlow {
         Self {
             events: Vec::new(),
             signal_stack: Vec::new(),
+            flushed: 0,
+            pid: None,
+        }
+    }
+}
+
+impl TraceHead {
+    /// Decode head from buf, which holds at least TE_SIZE bytes.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < TE_SIZE {
+            return None;
         }
+        let mut r = LeReader::new(buf);
+        Some(Self {
+            magic: r.u16(),
+            headsize: r.u16(),
+            totalsize: r.u32(),
+            inout: r.u64(),
+            cause: r.u64(),
+            epc: r.u64(),
+            ax: r.u64_array(),
+            usp: r.u64(),
+            stack: r.u64_array(),
+            orig_a0: r.u64(),
+            satp: r.u64(),
+            tp: r.u64(),
+            sscratch: r.u64(),
+        })
     }
 }
 
 const UTS_LEN: usize = 64;
 
-#[repr(C)]
 struct UTSName {
     fields: [[u8; UTS_LEN + 1]; 6],
 }
-const UTSNAME_SIZE: usize = mem::size_of::<UTSName>();
-
-#[derive(Debug)]
-#[repr(C)]
-pub struct KStat {
-    st_dev: u64,
-    st_ino: u64,
-    st_mode: u32,
-    st_nlink: u32,
-    st_uid: u32,
-    st_gid: u32,
-    st_rdev: u64,
-    _pad0: u64,
-    st_size: u64,
-    st_blksize: u32,
-    _pad1: u32,
-    st_blocks: u64,
-    st_atime_sec: isize,
-    st_atime_nsec: isize,
-    st_mtime_sec: isize,
-    st_mtime_nsec: isize,
-    st_ctime_sec: isize,
-    st_ctime_nsec: isize,
-}
-const KSTAT_SIZE: usize = mem::size_of::<KStat>();
+/// Size of struct new_utsname
+const UTSNAME_SIZE: usize = (UTS_LEN + 1) * 6;
 
-impl TraceEvent {
-    pub fn handle_syscall(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        match self.head.ax[7] {
-            SYS_IOCTL => self.do_common("ioctl", 3),
-            SYS_FCNTL => self.do_common("fcntl", 3),
-            SYS_DUP3 => self.do_common("dup3", 3),
-            SYS_FACCESSAT => self.do_faccessat(args),
-            SYS_MKDIRAT => self.do_common("mkdirat", 3),
-            SYS_GETCWD => self.do_getcwd(args),
-            SYS_CHDIR => self.do_chdir(args),
-            SYS_FCHMODAT => self.do_common("fchmodat", 4),
-            SYS_FCHOWNAT => self.do_common("fchownat", 5),
-            SYS_OPENAT => self.do_openat(args),
-            SYS_CLOSE => self.do_common("close", 1),
-            SYS_LSEEK => self.do_common("lseek", 3),
-            SYS_SENDFILE => self.do_common("sendfile", 4),
-            SYS_READ => self.do_read(args),
-            SYS_WRITE => self.do_write(args),
-            SYS_WRITEV => self.do_common("writev", 3),
-            SYS_UNLINKAT => self.do_unlinkat(args),
-            SYS_FSTATAT => self.do_fstatat(args),
-            SYS_EXIT_GROUP => self.do_common("exit_group", 1),
-            SYS_SET_TID_ADDRESS => self.do_set_tid_address(args),
-            SYS_SET_ROBUST_LIST => self.do_common("set_robust_list", 2),
-            SYS_CLOCK_GETTIME => self.do_common("clock_gettime", 2),
-            SYS_UNAME => self.do_uname(args),
-            SYS_BRK => self.do_brk(args),
-            SYS_MOUNT => self.do_common("mount", 5),
-            SYS_MSYNC => self.do_common("msync", 3),
-            SYS_MMAP => self.do_mmap(args),
-            SYS_MUNMAP => self.do_common("munmap", 2),
-            SYS_MPROTECT => self.do_mprotect(args),
-
-            SYS_PRLIMIT64 => self.do_common("prlimit64", 4),
-            SYS_GETRANDOM => self.do_common("getrandom", 3),
-            SYS_KILL=> self.do_kill(args),
-            SYS_RT_SIGACTION => self.do_rt_sigaction(args),
-            SYS_RT_SIGPROCMASK => self.do_rt_sigprocmask(args),
-            SYS_CLONE => self.do_clone(args),
-            SYS_EXECVE => self.do_execve(args),
-            SYS_GETTID => self.do_common("gettid", 0),
-            SYS_GETGID => self.do_common("getgid", 0),
-            SYS_GETEGID => self.do_common("getegid", 0),
-            SYS_GETPID => self.do_getpid(args),
-            SYS_GETPPID => self.do_getppid(args),
-            SYS_GETUID => self.do_common("getuid", 0),
-            SYS_GETEUID => self.do_common("geteuid", 0),
-            SYS_TGKILL => self.do_common("tgkill", 3),
-            SYS_WAIT4 => self.do_wait4(args),
-            SYS_GETDENTS64 => self.do_common("getdents64", 3),
-            _ => ("", 7, format!("{:#x}", self.result)),
+impl UTSName {
+    fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < UTSNAME_SIZE {
+            return None;
         }
-    }
-
-    fn do_brk(&self, _args: &mut Vec<String>) -> (&'static str, usize, String) {
-        ("brk", 1, format!("{:#x}", self.result))
-    }
-
-    fn do_set_tid_address(&self, _args: &mut Vec<String>) -> (&'static str, usize, String) {
-        let result = if self.level == 2 {
-            self.mask_tid(self.result)
-        } else {
-            format!("{:#x}", self.result)
-        };
-        ("set_tid_address", 1, result)
-    }
-
-    fn do_kill(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        if self.level == 2 {
-            args[0] = self.mask_tid(parse_usize(&args[0]) as i64);
+        let mut r = LeReader::new(buf);
+        let mut fields = [[0u8; UTS_LEN + 1]; 6];
+        for field in fields.iter_mut() {
+            *field = r.bytes();
         }
-        ("kill", 2, format!("{:#x}", self.result))
-    }
-
-    fn do_getpid(&self, _args: &mut Vec<String>) -> (&'static str, usize, String) {
-        let result = if self.level == 2 {
-            self.mask_tid(self.result)
-        } else {
-            format!("{:#x}", self.result)
-        };
-        ("getpid", 0, result)
+        Some(Self { fields })
     }
+}
 
-    fn do_getppid(&self, _args: &mut Vec<String>) -> (&'static str, usize, String) {
-        let result = if self.level == 2 {
-            self.mask_tid(self.result)
-        } else {
-            format!("{:#x}", self.result)
-        };
-        ("getppid", 0, result)
-    }
+/// struct stat of riscv64
+#[derive(Debug)]
+pub struct KStat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    pub st_size: u64,
+    pub st_blksize: u32,
+    pub st_blocks: u64,
+    pub st_atime_sec: i64,
+    pub st_atime_nsec: i64,
+    pub st_mtime_sec: i64,
+    pub st_mtime_nsec: i64,
+    pub st_ctime_sec: i64,
+    pub st_ctime_nsec: i64,
+}
+/// Size of struct stat of riscv64, including 2 unused ints at the end
+const KSTAT_SIZE: usize = 128;
 
-    fn do_wait4(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        let result = if self.level == 2 {
-            args[0] = self.mask_tid(parse_usize(&args[0]) as i64);
-            self.mask_tid(self.result)
-        } else {
-            format!("{:#x}", self.result)
-        };
-        ("wait4", 4, result)
+impl KStat {
+    fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < KSTAT_SIZE {
+            return None;
+        }
+        let mut r = LeReader::new(buf);
+        Some(Self {
+            st_dev: r.u64(),
+            st_ino: r.u64(),
+            st_mode: r.u32(),
+            st_nlink: r.u32(),
+            st_uid: r.u32(),
+            st_gid: r.u32(),
+            st_rdev: r.u64(),
+            st_size: r.skip(8).u64(),
+            st_blksize: r.u32(),
+            st_blocks: r.skip(4).u64(),
+            st_atime_sec: r.i64(),
+            st_atime_nsec: r.i64(),
+            st_mtime_sec: r.i64(),
+            st_mtime_nsec: r.i64(),
+            st_ctime_sec: r.i64(),
+            st_ctime_nsec: r.i64(),
+        })
     }
+}
 
-    fn do_clone(&self, _args: &mut Vec<String>) -> (&'static str, usize, String) {
-        let result = if self.result != 0 && self.level == 2 {
-            self.mask_tid(self.result)
+impl TraceEvent {
+    /// Format argument of index by the type of its parameter.
+    fn format_arg(&self, index: usize, arg: &Arg) -> String {
+        let raw = self.head.ax[index];
+        let value = if self.raw_fmt {
+            format!("{:#x}", raw)
         } else {
-            format!("{:#x}", self.result)
+            match arg.param {
+                Param::Int => format!("{:#x}", raw),
+                Param::Dirfd if raw as i32 == AT_FDCWD => "AT_FDCWD".to_string(),
+                Param::Fd | Param::Dirfd => self.format_fd(index, raw as i32),
+                Param::Path => match self.payload(index) {
+                    Some(payload) => match c_str(&payload.data) {
+                        Some(name) => format!("\"{}\"", name),
+                        None => "[!parse_str_err!]".to_string(),
+                    },
+                    None => format_ptr(raw),
+                },
+                Param::Flags(kind) => flags_name(kind, raw),
+                Param::Signal => signal_name(raw),
+                // pid_t is int
+                Param::Pid => self.mask_pid(raw as i32 as i64),
+                Param::Ptr => format_ptr(raw),
+                Param::Size => raw.to_string(),
+                Param::Struct(kind) => self.format_struct(index, kind)
+                    .unwrap_or_else(|| format_ptr(raw)),
+                Param::Buf => {
+                    // Only what is read from stdin, or written to stdout
+                    // and stderr, is worth printing as string
+                    let fd = self.head.ax[0];
+                    let stdio = match self.head.ax[7] {
+                        SYS_READ => fd == 0,
+                        _ => fd == 1 || fd == 2,
+                    };
+                    match self.payload(index) {
+                        Some(payload) if stdio => debug_str(&payload.data),
+                        _ => format!("{:#x}", raw),
+                    }
+                },
+            }
         };
-        ("clone", 5, result)
-    }
-
-    fn mask_tid(&self, oid: i64) -> String {
-        let mut tid_map = TID_MAP.lock().unwrap();
-        if let Some(tid) = tid_map.get(&oid) {
-            tid.clone()
+        if arg.labelled {
+            format!("{}: {}", arg.name, value)
         } else {
-            let tid = format!("tid_{}", tid_map.len());
-            tid_map.insert(oid, tid.clone());
-            tid
+            value
         }
     }
 
-    #[inline]
-    fn do_common(&self, name: &'static str, argc: usize) -> (&'static str, usize, String) {
-        if self.result <= 0 {
-            (name, argc, format!("{}", errno_name(self.result)))
-        } else {
-            (name, argc, format!("{:#x}", self.result))
+    /// Format fd of argument of index with its path if it's known,
+    /// e.g. `3</etc/passwd>` as `strace -y` does.
+    fn format_fd(&self, index: usize, fd: i32) -> String {
+        match self.fd_paths.iter().find(|(idx, _)| *idx == index) {
+            Some((_, path)) => format!("{}<{}>", fd, path),
+            None => fd.to_string(),
         }
     }
 
-    fn do_path(&self, args: &mut Vec<String>, index: usize) {
-        assert!(self.payloads.len() >= 1);
-        let payload = &self.payloads.first().unwrap();
-        //assert_eq!(payload.inout, crate::IN);
-        assert_eq!(payload.index, index);
-        let fname = CStr::from_bytes_until_nul(&payload.data).unwrap();
-        let fname = match fname.to_str() {
-            Ok(name) => {
-                format!("\"{}\"", name)
-            }
-            Err(_) => "[!parse_str_err!]".to_string(),
-        };
-        args[payload.index] = fname;
-    }
-
-    fn do_openat(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        if self.head.ax[0] == AT_FDCWD {
-            args[0] = "AT_FDCWD".to_string();
+    /// Format struct of argument of index from its payload, if it's
+    /// there and can be decoded.
+    fn format_struct(&self, index: usize, kind: StructKind) -> Option<String> {
+        if kind == StructKind::Strings {
+            let strings = self.payloads
+                .iter()
+                .filter(|payload| payload.index == index)
+                .map(|payload| debug_str(&payload.data))
+                .collect::<Vec<_>>();
+            return Some(format!("{{{}}}", strings.join(", ")));
         }
-        self.do_path(args, 1);
-        self.do_common("openat", 4)
-    }
-
-    fn do_getcwd(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        self.do_path(args, 0);
-        self.do_common("getcwd", 2)
-    }
-
-    fn do_chdir(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        self.do_path(args, 0);
-        self.do_common("chdir", 1)
-    }
-
-    fn do_faccessat(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        if self.head.ax[0] == AT_FDCWD {
-            args[0] = "AT_FDCWD".to_string();
+        let payload = self.payload(index)?;
+        match kind {
+            // Stat is only filled in on success
+            StructKind::Stat if self.result == 0 => self.handle_stat(payload),
+            StructKind::Stat => None,
+            StructKind::Utsname => self.handle_utsname(payload),
+            StructKind::Sigaction => SigAction::decode(&payload.data).map(|act| act.to_string()),
+            StructKind::Sigset => decode_sigset(&payload.data).map(|set| format!("{:#x}", set)),
+            StructKind::Strings => unreachable!(),
         }
-        self.do_path(args, 1);
-        // For faccessat, there're 3 args, NO 'flags'.
-        // For faccessat2, there're 4 args with 'flags'.
-        self.do_common("faccessat", 3)
     }
 
-    fn do_unlinkat(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        if self.head.ax[0] == AT_FDCWD {
-            args[0] = "AT_FDCWD".to_string();
+    /// Format result by its type. Only -4095..-1 are errors, e.g. a
+    /// large offset of lseek may look negative.
+    fn format_ret(&self, ret: Ret) -> String {
+        match ret {
+            _ if is_error(self.result) => errno_name(self.result).to_string(),
+            Ret::Status if self.result == 0 => String::from("OK"),
+            Ret::Status | Ret::Int | Ret::Addr => format!("{:#x}", self.result),
+            Ret::Count | Ret::Fd => self.result.to_string(),
+            Ret::Pid => self.mask_pid(self.result),
         }
-        self.do_path(args, 1);
-        self.do_common("unlinkat", 3)
     }
 
-    fn do_fstatat(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        if self.head.ax[0] == AT_FDCWD {
-            args[0] = "AT_FDCWD".to_string();
-        }
-        self.do_path(args, 1);
-        if self.result == 0 {
-            assert_eq!(self.payloads.len(), 2);
-            for payload in &self.payloads {
-                if payload.index == 2 {
-                    args[payload.index] = self.handle_stat(payload);
-                }
-            }
-        }
-        self.do_common("fstatat", 4)
+    /// Pid/tid in signed decimal, as strace prints it. For level 2,
+    /// replace a positive one with its seq-name. Zero and negative
+    /// values (e.g. 'any child' for wait4, errors) keep their raw form.
+    fn mask_pid(&self, pid: i64) -> String {
+        self.names.pid(pid).unwrap_or_else(|| pid.to_string())
     }
 
-    fn handle_stat(&self, payload: &TracePayload) -> String {
-        assert_eq!(payload.inout, crate::OUT);
-        assert_eq!(payload.index, 2);
-        let mut buf = [0u8; KSTAT_SIZE];
-        buf.clone_from_slice(&payload.data[..KSTAT_SIZE]);
+    pub(crate) fn payload(&self, index: usize) -> Option<&TracePayload> {
+        self.payloads.iter().find(|payload| payload.index == index)
+    }
 
-        let k = unsafe { mem::transmute::<[u8; KSTAT_SIZE], KStat>(buf) };
-        if self.level != 2 {
+    fn handle_stat(&self, payload: &TracePayload) -> Option<String> {
+        let k = KStat::decode(&payload.data)?;
+        let stat = if self.level != 2 {
             format!(
-                "{{dev={:#x}, ino={}, mode={:#o}, nlink={}, rdev={}, size={}, blksize={}, blocks={}}}",
+                "{{dev={:#x}, ino={}, mode={}, nlink={}, rdev={}, size={}, blksize={}, blocks={}}}",
                 k.st_dev,
                 k.st_ino,
-                k.st_mode,
+                mode_name(k.st_mode.into()),
                 k.st_nlink,
                 k.st_rdev,
                 k.st_size,
@@ -338,182 +333,128 @@ impl TraceEvent {
             )
         } else {
             format!(
-                "{{dev, ino, mode={:#o}, nlink={}, rdev={}, size={}, blksize, blocks={}}}",
-                k.st_mode,
+                "{{dev, ino, mode={}, nlink={}, rdev={}, size={}, blksize, blocks={}}}",
+                mode_name(k.st_mode.into()),
                 k.st_nlink,
                 k.st_rdev,
                 k.st_size,
                 k.st_blocks
             )
-        }
+        };
+        Some(stat)
     }
 
-    fn do_uname(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        assert_eq!(self.payloads.len(), 1);
-        let payload = &self.payloads.first().unwrap();
-        assert_eq!(payload.inout, crate::OUT);
-        assert_eq!(payload.index, 0);
-        let mut buf = [0u8; UTSNAME_SIZE];
-        buf.clone_from_slice(&payload.data[..UTSNAME_SIZE]);
-
-        let utsname = unsafe { mem::transmute::<[u8; UTSNAME_SIZE], UTSName>(buf) };
-
+    fn handle_utsname(&self, payload: &TracePayload) -> Option<String> {
+        let utsname = UTSName::decode(&payload.data)?;
         let mut names = Vec::with_capacity(6);
         for i in 0..utsname.fields.len() {
             if self.level == 2 && i == 3 {
                 names.push("%timestamp%".to_string());
                 continue;
             }
-            let fname = CStr::from_bytes_until_nul(&utsname.fields[i][..]).unwrap();
-            names.push(format!("{:?}", fname));
-        }
-        let r_uname = names.join(", ");
-        args[payload.index] = format!("{{{}}}", r_uname);
-        ("uname", 1, format!("{:#x}", self.result))
-    }
-
-    fn do_mmap(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        if !self.raw_fmt {
-            if self.head.ax[0] == 0 {
-                args[0] = String::from("NULL");
-            }
-            args[2] = prot_name(self.head.ax[2]);
-            args[3] = map_name(self.head.ax[3]);
-            if self.head.ax[4] == u64::MAX {
-                args[4] = "-1".to_string();
-            }
-        }
-        if self.result <= 0 {
-            ("mmap", 6, String::from("MAP_FAILED")) // On error, the value MAP_FAILED(that is, (void *) -1) is returned,
-        } else {
-            ("mmap", 6, format!("{:#x}", self.result)) // On success, mmap() returns a pointer to the mapped area.
+            names.push(debug_str(&utsname.fields[i][..]));
         }
+        Some(format!("{{{}}}", names.join(", ")))
     }
+}
 
-    fn do_rt_sigaction(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        let signum = self.head.ax[0];
-        args[0] = sig_name(signum);
+/// String up to nul, if it's UTF-8.
+fn c_str(data: &[u8]) -> Option<&str> {
+    CStr::from_bytes_until_nul(data).ok()?.to_str().ok()
+}
 
-        if let Some((sig_action, index)) = parse_sigaction(self) {
-            args[index] = sig_action.to_string();
-        }
-        ("rt_sigaction", 3, format!("{:#x}", self.result))
-    }
-
-    fn do_rt_sigprocmask(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        args[0] = match self.head.ax[0] {
-            SIG_BLOCK => "SIG_BLOCK",
-            SIG_UNBLOCK => "SIG_UNBLOCK",
-            SIG_SETMASK => "SIG_SETMASK",
-            _ => panic!("bad how"),
-        }.to_string();
-
-        let mut index = 0;
-        if self.head.ax[1] != 0 {
-            let payload = self.payloads.get(index).unwrap();
-            index += 1;
-            let mut buf = [0u8; 8];
-            buf.clone_from_slice(&payload.data[..8]);
-            let nset = unsafe { mem::transmute::<[u8; 8], u64>(buf) };
-            args[1] = format!("nset: {:#x}", nset);
-        } else {
-            args[1] = format!("nset: NULL");
-        }
-        if self.head.ax[2] != 0 {
-            let payload = self.payloads.get(index).unwrap();
-            index += 1;
-            let mut buf = [0u8; 8];
-            buf.clone_from_slice(&payload.data[..8]);
-            let oset = unsafe { mem::transmute::<[u8; 8], u64>(buf) };
-            args[2] = format!("oset: {:#x}", oset);
-        } else {
-            args[2] = format!("oset: NULL");
-        }
-        assert_eq!(index, self.payloads.len());
-        ("rt_sigprocmask", 4, format!("{:#x}", self.result))
+/// String up to nul, quoted and escaped.
+fn debug_str(data: &[u8]) -> String {
+    match CStr::from_bytes_until_nul(data) {
+        Ok(s) => format!("{:?}", s),
+        Err(_) => "[!parse_str_err!]".to_string(),
     }
+}
 
-    fn do_mprotect(&self,args: &mut Vec<String>) -> (&'static str, usize, String) {
-        if self.head.ax[0] == 0 {
-            args[0] = String::from("NULL");
-        }
-        args[2] = prot_name(self.head.ax[2]);
-        if self.result <= 0 {
-            ("mprotect", 3, format!("{}", errno_name(self.result)))
-        } else {
-            ("mprotect", 3, format!("{:#x}", self.result))
-        }
+fn format_ptr(ptr: u64) -> String {
+    if ptr == 0 {
+        String::from("NULL")
+    } else {
+        format!("{:#x}", ptr)
     }
+}
 
-    fn do_write(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        args[0] = format!("{}", self.head.ax[0] as isize); // fd
-        if self.head.ax[0] == 1 || self.head.ax[0] == 2 {
-            if self.payloads.len() == 1 {
-                let payload = &self.payloads.first().unwrap();
-                assert_eq!(payload.inout, crate::OUT);
-                assert_eq!(payload.index, 1);
-                args[payload.index] = match CStr::from_bytes_until_nul(&payload.data) {
-                    Ok(content) => {
-                        format!("{:?}", content)
-                    }
-                    Err(_) => "[!parse_str_err!]".to_string(),
-                };
-            }
-        }
+fn flags_name(kind: FlagsKind, flags: u64) -> String {
+    match kind {
+        FlagsKind::Prot => prot_name(flags),
+        FlagsKind::Map => map_name(flags),
+        FlagsKind::Clone => clone_flags_name(flags),
+        FlagsKind::SigHow => match flags {
+            SIG_BLOCK => "SIG_BLOCK".to_string(),
+            SIG_UNBLOCK => "SIG_UNBLOCK".to_string(),
+            SIG_SETMASK => "SIG_SETMASK".to_string(),
+            how => format!("{:#x}", how),
+        },
+        FlagsKind::Open => open_flags_name(flags),
+        FlagsKind::Mode => mode_name(flags),
+        FlagsKind::Access => access_mode_name(flags),
+        FlagsKind::At => at_flags_name(flags),
+    }
+}
 
-        ("write", 3, format!("{:#x}", self.result))
+/// Name of signal argument. Zero (e.g. kill to check a pid) and
+/// unknown signals are left as numbers.
+fn signal_name(signum: u64) -> String {
+    match sig_name(signum) {
+        _ if signum == 0 => "0".to_string(),
+        name if name == "SIGUNKNOWN" => format!("{:#x}", signum),
+        name => name,
     }
+}
 
-    fn do_read(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        args[0] = format!("{}", self.head.ax[0] as isize); // fd
-        if self.head.ax[0] == 0 {
-            if self.payloads.len() == 1 {
-                let payload = &self.payloads.first().unwrap();
-                assert_eq!(payload.inout, crate::OUT);
-                assert_eq!(payload.index, 1);
+/// Syscall of an event, with arguments and result decoded for output.
+#[derive(Clone, Debug)]
+pub struct Syscall {
+    /// Name of syscall, or `sys_N` if it's unknown
+    pub name: String,
+    pub args: Vec<String>,
+    /// Names of parameters, as in man pages
+    pub arg_names: Vec<&'static str>,
+    pub ret: String,
+}
 
-                args[payload.index] = match CStr::from_bytes_until_nul(&payload.data) {
-                    Ok(content) => {
-                        format!("{:?}", content)
-                    }
-                    Err(_) => "[!parse_str_err!]".to_string(),
-                };
-            }
+impl TraceEvent {
+    /// Decode syscall of event by its signature, as [`Display`]
+    /// prints it. None if event isn't an ecall from user mode, e.g.
+    /// signal delivery.
+    pub fn syscall(&self) -> Option<Syscall> {
+        if self.head.cause != USER_ECALL {
+            return None;
         }
-
-        ("read", 3, format!("{:#x}", self.result))
+        let sig = signature(self.head.ax[7]);
+        // Mode of openat is garbage without O_CREAT or O_TMPFILE
+        let params = match self.head.ax[7] {
+            SYS_OPENAT if !open_has_mode(self.head.ax[2]) => &sig.args[..3],
+            _ => sig.args,
+        };
+        let args = params
+            .iter()
+            .enumerate()
+            .map(|(index, arg)| self.format_arg(index, arg))
+            .collect();
+        let name = if !sig.name.is_empty() {
+            sig.name.to_owned()
+        } else {
+            format!("sys_{}", self.head.ax[7])
+        };
+        let arg_names = params.iter().map(|arg| arg.name).collect();
+        Some(Syscall { name, args, arg_names, ret: self.format_ret(sig.ret) })
     }
 
-    fn do_execve(&self, args: &mut Vec<String>) -> (&'static str, usize, String) {
-        let mut argv = Vec::new();
-        let mut envp = Vec::new();
-        for payload in &self.payloads {
-            if payload.index == 0 {
-                args[payload.index] = match CStr::from_bytes_until_nul(&payload.data) {
-                    Ok(content) => {
-                        format!("{:?}", content)
-                    }
-                    Err(_) => "[!parse_str_err!]".to_string(),
-                };
-            }else if payload.index == 1 {
-                argv.push(match CStr::from_bytes_until_nul(&payload.data) {
-                    Ok(content) => {
-                        format!("{:?}", content)
-                    }
-                    Err(_) => "[!parse_str_err!]".to_string(),
-                })
-            }else if payload.index == 2 {
-                envp.push(match CStr::from_bytes_until_nul(&payload.data) {
-                    Ok(content) => {
-                        format!("{:?}", content)
-                    }
-                    Err(_) => "[!parse_str_err!]".to_string(),
-                })
-            }
-        }
-        args[1] = format!("{{{}}}", argv.join(", "));
-        args[2] = format!("{{{}}}", envp.join(", "));
-        ("execve",3, format!("{:#x}", self.result))
+    /// Paths which arguments refer to: path strings from payloads, and
+    /// paths of fds from fd tables.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        let strings = signature(self.head.ax[7]).args
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| arg.param == Param::Path)
+            .filter_map(|(index, _)| c_str(&self.payload(index)?.data));
+        strings.chain(self.fd_paths.iter().map(|(_, path)| path.as_str()))
     }
 }
 
@@ -528,26 +469,16 @@ impl Display for TraceEvent {
             },
             _ => (),
         }
-        assert_eq!(self.head.cause, USER_ECALL);
 
-        let mut args = self.head.ax[..7]
-            .iter()
-            .map(|arg| format!("{:#x}", arg))
-            .collect::<Vec<_>>();
-
-        let (sysname, argc, result) = self.handle_syscall(&mut args);
-        let sysname = if sysname.len() > 0 {
-            sysname.to_owned()
-        } else {
-            format!("sys_{}", self.head.ax[7])
+        let Some(call) = self.syscall() else {
+            return write!(fmt, "[!cause {:#x}!], usp: {:#x}", self.head.cause, self.head.usp);
         };
-
         write!(
             fmt,
             "{}({}) -> {}, usp: {:#x}",
-            sysname,
-            args[..argc].join(", "),
-            result,
+            call.name,
+            call.args.join(", "),
+            call.ret,
             self.head.usp
         )
     }
@@ -555,30 +486,344 @@ impl Display for TraceEvent {
 
 pub fn parse_sigaction(evt: &TraceEvent) -> Option<(SigAction, usize)> {
     let payload = evt.payloads.first()?;
-    let mut buf = [0u8; 24];
-    buf.clone_from_slice(&payload.data[..24]);
-    let sigaction = unsafe { mem::transmute::<[u8; 24], SigAction>(buf) };
+    let sigaction = SigAction::decode(&payload.data)?;
     Some((sigaction, payload.index))
 }
 
-pub fn print_events(tid: u64, events: &Vec<TraceEvent>) {
-    println!("Task[{:#x}] ========>", tid);
+/// Decode sigset_t (64 signals of riscv64).
+fn decode_sigset(buf: &[u8]) -> Option<u64> {
+    if buf.len() < 8 {
+        return None;
+    }
+    Some(LeReader::new(buf).u64())
+}
+
+/// Seq-names (tid_N) which replace ids (task pointers, pids and tids)
+/// at level 2, owned by one analysis and shared by its clones.
+///
+/// [`crate::level1::Grouper`] names ids as it reads events, so names
+/// are given in order of first appearance in trace, whatever order
+/// events are printed in. Then identical workloads produce identical
+/// output. Below level 2 ids keep their raw form.
+#[derive(Clone, Debug, Default)]
+pub struct SeqNames(Option<Arc<Mutex<HashMap<i64, String>>>>);
+
+impl SeqNames {
+    /// Names for parse level, which only name ids at level 2.
+    pub fn new(level: usize) -> Self {
+        if level == 2 {
+            Self(Some(Arc::default()))
+        } else {
+            Self(None)
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Seq-name of id, which is given now if id has none.
+    fn name(&self, id: i64) -> Option<String> {
+        let mut map = self.0.as_ref()?.lock().unwrap();
+        let len = map.len();
+        Some(map.entry(id).or_insert_with(|| format!("tid_{}", len)).clone())
+    }
+
+    /// Give id a name in order of appearance, if it has none.
+    pub fn add(&self, id: i64) {
+        self.name(id);
+    }
+
+    /// Name of task (sscratch): its seq-name, or hex below level 2.
+    pub fn task(&self, tid: u64) -> String {
+        self.name(tid as i64).unwrap_or_else(|| format!("{:#x}", tid))
+    }
+
+    /// Seq-name of a positive pid or tid. Zero and negative values
+    /// (e.g. 'any child' for wait4, errors) have none.
+    pub fn pid(&self, pid: i64) -> Option<String> {
+        if pid > 0 { self.name(pid) } else { None }
+    }
+}
+
+/// Print events of task one per line, tagged with task name.
+/// Used in streaming mode, where events of tasks interleave.
+pub fn print_task_events(task: &str, flow: &TraceFlow) {
+    print!("{}", format_task_events(task, flow));
+}
+
+/// Format events as [`print_task_events`] prints them.
+pub fn format_task_events(task: &str, flow: &TraceFlow) -> String {
+    let mut out = String::new();
+    for (idx, evt) in flow.events.iter().enumerate() {
+        let _ = writeln!(out, "Task[{}] [{}]: {}", task, flow.flushed + idx, evt);
+    }
+    out
+}
+
+/// Report data skipped by a lenient reader, and events dropped because
+/// their requests or replies were lost.
+pub fn print_skipped(skipped: Skipped, dropped: usize) {
+    if skipped.bytes > 0 || dropped > 0 {
+        println!("Skipped: {} bytes, {} events; dropped {} unmatched events",
+            skipped.bytes, skipped.events, dropped);
+    }
+}
+
+pub fn print_events(task: &str, events: &[TraceEvent]) {
+    print!("{}", format_events(task, events));
+}
+
+/// Format events as [`print_events`] prints them.
+pub fn format_events(task: &str, events: &[TraceEvent]) -> String {
+    let mut out = format!("Task[{}] ========>\n", task);
     for (idx, evt) in events.iter().enumerate() {
-        println!("[{}]: {}", idx, evt);
+        let _ = writeln!(out, "[{}]: {}", idx, evt);
+    }
+    out.push('\n');
+    out
+}
+
+/// Sequential reader of trace events from a data file.
+///
+/// Each item is one raw event, either a request (IN) or a reply (OUT).
+/// Iteration stops after the first error, unless the reader is lenient.
+pub struct EventReader {
+    source: Source,
+    offset: u64,
+    end: u64,
+    level: usize,
+    lenient: bool,
+    skipped: Skipped,
+    /// Number of the next event in file
+    seq: u64,
+    select: Selection,
+    /// Numbers and offsets of selected events, looked up in index
+    offsets: Option<VecDeque<(u64, u64)>>,
+}
+
+enum Source {
+    File(BufReader<File>),
+    /// Events decoded from views of mapped data, already selected
+    Mapped(Box<Decoded<TraceEvent>>),
+}
+
+/// Damaged data which a lenient reader has skipped.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Skipped {
+    pub bytes: usize,
+    /// Number of damaged regions, each one counts as an event
+    pub events: usize,
+}
+
+/// Event head starts with LK_MAGIC and TE_SIZE, both little endian.
+pub(crate) const HEAD_SIG: [u8; 4] = [
+    LK_MAGIC as u8, (LK_MAGIC >> 8) as u8, TE_SIZE as u8, (TE_SIZE >> 8) as u8
+];
+
+const SCAN_CHUNK: usize = 64 * 1024;
+
+impl EventReader {
+    /// Open trace data file. Level is passed to each event and decides
+    /// how it is formatted.
+    pub fn open(path: &str, level: usize) -> Result<Self> {
+        let f = File::open(path)?;
+        let end = f.metadata()?.len();
+        Ok(Self::new(Source::File(BufReader::new(f)), end, level))
+    }
+
+    /// Open trace data file by mapping it into memory, and decode
+    /// events on `opts.jobs` threads. Events are the same as
+    /// [`Self::open`] with lenient and selection of opts, which are
+    /// applied while finding events, or by index if it's given. Their
+    /// payloads are left in the map.
+    pub fn open_mapped(path: &str, opts: &Options, index: Option<&TraceIndex>) -> Result<Self> {
+        let level = opts.level;
+        let decoded = Decoded::open(path, opts, index, Box::new(move |seq, view| {
+            let mut evt = view.into_event(level)?;
+            evt.seq = seq;
+            Ok(evt)
+        }))?;
+        Ok(Self::new(Source::Mapped(Box::new(decoded)), 0, level))
+    }
+
+    fn new(source: Source, end: u64, level: usize) -> Self {
+        Self {
+            source,
+            offset: 0,
+            end,
+            level,
+            lenient: false,
+            skipped: Skipped::default(),
+            seq: 0,
+            select: Selection::default(),
+            offsets: None,
+        }
+    }
+
+    /// Skip damaged or truncated events instead of failing. The reader
+    /// scans forward for the next valid head and goes on from there.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Only read selected events. With an index of the data file, the
+    /// reader seeks to them directly; otherwise it reads all events
+    /// and skips those not selected.
+    pub fn select(mut self, select: Selection, index: Option<&TraceIndex>) -> Self {
+        if let Some(index) = index {
+            self.offsets = Some(index.lookup(&select).into());
+        }
+        self.select = select;
+        self
+    }
+
+    /// Data skipped so far.
+    pub fn skipped(&self) -> Skipped {
+        match &self.source {
+            Source::Mapped(decoded) => decoded.skipped(),
+            Source::File(_) => self.skipped,
+        }
+    }
+
+    fn seek(&mut self, offset: u64) -> Result<()> {
+        if let Source::File(reader) = &mut self.source {
+            reader.seek(SeekFrom::Start(offset))?;
+        }
+        self.offset = offset;
+        Ok(())
+    }
+
+    fn parse_next(&mut self) -> Result<TraceEvent> {
+        let start = self.offset;
+        let left = (self.end - start) as usize;
+        match &mut self.source {
+            Source::File(reader) => parse_event(reader, start, left, self.level),
+            Source::Mapped(_) => unreachable!("mapped reader is not parsed in place"),
+        }
+    }
+
+    /// Skip the damaged event at start, and move to the next valid head.
+    fn resync(&mut self, start: u64) -> Result<()> {
+        let end = self.end;
+        let next = match &mut self.source {
+            Source::File(reader) => find_head(reader, start + 1, end)?.unwrap_or(end),
+            Source::Mapped(_) => unreachable!("mapped reader is not parsed in place"),
+        };
+        self.skipped.bytes += (next - start) as usize;
+        self.skipped.events += 1;
+        self.seek(next)
+    }
+
+    /// Stop iteration after an error.
+    fn stop(&mut self) {
+        self.offset = self.end;
+        self.offsets = Some(VecDeque::new());
+    }
+}
+
+/// Scan file for the first valid event head in [from, end).
+fn find_head(reader: &mut BufReader<File>, from: u64, end: u64) -> Result<Option<u64>> {
+    let mut buf = vec![0u8; SCAN_CHUNK];
+    let mut pos = from;
+    while pos + TE_SIZE as u64 <= end {
+        let n = SCAN_CHUNK.min((end - pos) as usize);
+        reader.seek(SeekFrom::Start(pos))?;
+        reader.read_exact(&mut buf[..n])?;
+        for i in 0..=(n - HEAD_SIG.len()) {
+            if buf[i..i + HEAD_SIG.len()] == HEAD_SIG {
+                let candidate = pos + i as u64;
+                if valid_head_at(reader, candidate, end)? {
+                    return Ok(Some(candidate));
+                }
+            }
+        }
+        pos += (n - HEAD_SIG.len() + 1) as u64;
+    }
+    Ok(None)
+}
+
+fn valid_head_at(reader: &mut BufReader<File>, offset: u64, end: u64) -> Result<bool> {
+    if end - offset < TE_SIZE as u64 {
+        return Ok(false);
+    }
+    let mut buf = [0u8; TE_SIZE];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buf)?;
+    let head = TraceHead::decode(&buf).unwrap();
+    Ok(check_head(&head, offset).is_ok() && head.totalsize as u64 <= end - offset)
+}
+
+impl Iterator for EventReader {
+    type Item = Result<TraceEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Source::Mapped(decoded) = &mut self.source {
+            return decoded.next();
+        }
+        loop {
+            if let Some(offsets) = &mut self.offsets {
+                let (seq, offset) = offsets.pop_front()?;
+                if let Err(e) = self.seek(offset) {
+                    self.stop();
+                    return Some(Err(e));
+                }
+                self.seq = seq;
+            } else if self.offset >= self.end || self.select.after(self.seq) {
+                return None;
+            }
+            let start = self.offset;
+            match self.parse_next() {
+                Ok(mut evt) => {
+                    self.offset += evt.head.totalsize as u64;
+                    let seq = self.seq;
+                    self.seq += 1;
+                    evt.seq = seq;
+                    if self.offsets.is_none() && !self.select.matches(seq, &evt.head) {
+                        continue;
+                    }
+                    return Some(Ok(evt));
+                },
+                Err(e) if self.lenient && !matches!(e, TraceError::Io(_)) => {
+                    warn!("{}, skip it", e);
+                    if self.offsets.is_some() {
+                        self.skipped.events += 1;
+                    } else if let Err(e) = self.resync(start) {
+                        self.stop();
+                        return Some(Err(e));
+                    }
+                },
+                Err(e) => {
+                    self.stop();
+                    return Some(Err(e));
+                },
+            }
+        }
     }
-    println!();
 }
 
-pub fn parse_event(reader: &mut BufReader<File>, level: usize) -> Result<TraceEvent> {
+/// Parse the event (head and payloads) at offset from reader.
+/// Left is the number of bytes from offset to the end of data.
+pub fn parse_event<R: Read>(
+    reader: &mut R, offset: u64, left: usize, level: usize
+) -> Result<TraceEvent> {
+    if left < TE_SIZE {
+        return Err(TraceError::Truncated { offset, need: TE_SIZE, left });
+    }
     let mut buf = [0u8; TE_SIZE];
     reader.read_exact(&mut buf)?;
-    let head = unsafe { mem::transmute::<[u8; TE_SIZE], TraceHead>(buf) };
-    assert_eq!(head.cause, USER_ECALL);
+    let head = TraceHead::decode(&buf).unwrap();
+    check_head(&head, offset)?;
+    if head.totalsize as usize > left {
+        return Err(TraceError::Truncated { offset, need: head.totalsize as usize, left });
+    }
 
     debug!("a7: {} total: {}", head.ax[7], head.totalsize);
     let payloads = if head.totalsize as usize > head.headsize as usize {
         parse_payloads(
             reader,
+            offset + head.headsize as u64,
             head.inout,
             head.totalsize as usize - head.headsize as usize,
         )?
@@ -588,18 +833,267 @@ pub fn parse_event(reader: &mut BufReader<File>, level: usize) -> Result<TraceEv
 
     let evt = TraceEvent {
         head,
+        offset,
+        seq: 0,
+        reply_seq: None,
         result: 0,
         payloads,
         signal: SigStage::Empty,
+        fd_paths: vec![],
+        names: SeqNames::default(),
         raw_fmt: false,
-        level: level,
+        level,
     };
     debug!("ok!");
     Ok(evt)
 }
 
-fn parse_usize(s: &str) -> usize {
-    let s = s.trim();
-    assert!(s.starts_with("0x"), "input: {}", s);
-    usize::from_str_radix(&s[2..], 16).unwrap()
+pub(crate) fn check_head(head: &TraceHead, offset: u64) -> Result<()> {
+    if head.magic != LK_MAGIC {
+        return Err(TraceError::BadMagic { offset, magic: head.magic });
+    }
+    if head.headsize as usize != TE_SIZE || head.totalsize < head.headsize as u32 {
+        return Err(TraceError::BadHeadSize {
+            offset,
+            headsize: head.headsize,
+            totalsize: head.totalsize,
+        });
+    }
+    if head.inout != crate::IN && head.inout != crate::OUT {
+        return Err(TraceError::BadInout { offset, inout: head.inout });
+    }
+    if head.cause != USER_ECALL {
+        return Err(TraceError::BadCause { offset, cause: head.cause });
+    }
+    if head.ax[7] >= MAX_SYSCALL_NBR {
+        return Err(TraceError::BadSysno { offset, sysno: head.ax[7] });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Cursor;
+    use std::path::PathBuf;
+    use super::*;
+    use crate::{IN, OUT};
+
+    /// Event of sscratch 0x1000 on disk, with payloads of (index, data).
+    fn record(inout: u64, ax: [u64; 8], payloads: &[(u16, &[u8])]) -> Vec<u8> {
+        task_record(0x1000, inout, ax, payloads)
+    }
+
+    /// Event of task (sscratch) on disk, with payloads of (index, data).
+    pub(crate) fn task_record(tid: u64, inout: u64, ax: [u64; 8], payloads: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut body = vec![];
+        for (index, data) in payloads {
+            body.extend(0xDCBAu16.to_le_bytes());
+            body.extend(index.to_le_bytes());
+            body.extend((data.len() as u32).to_le_bytes());
+            body.extend(*data);
+        }
+        let mut buf = vec![];
+        buf.extend(LK_MAGIC.to_le_bytes());
+        buf.extend((TE_SIZE as u16).to_le_bytes());
+        buf.extend(((TE_SIZE + body.len()) as u32).to_le_bytes());
+        // inout, cause, epc
+        for v in [inout, USER_ECALL, 0x1000] {
+            buf.extend(v.to_le_bytes());
+        }
+        ax.iter().for_each(|v| buf.extend(v.to_le_bytes()));
+        // usp, stack[8], orig_a0, satp, tp, sscratch
+        let rest = [[0x3ffffffa40].as_slice(), &[0; 8], &[ax[0], 0x8000000000081234, 0, tid]];
+        rest.concat().iter().for_each(|v| buf.extend(v.to_le_bytes()));
+        assert_eq!(buf.len(), TE_SIZE);
+        buf.extend(body);
+        buf
+    }
+
+    fn getpid(inout: u64) -> Vec<u8> {
+        record(inout, [0, 0, 0, 0, 0, 0, 0, SYS_GETPID], &[])
+    }
+
+    /// Write data into a file of test, which is removed on drop.
+    pub(crate) struct TempTrace(PathBuf);
+
+    impl TempTrace {
+        pub(crate) fn new(name: &str, data: &[u8]) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("lktrace-{}-{}.data", std::process::id(), name));
+            std::fs::write(&path, data).unwrap();
+            Self(path)
+        }
+
+        pub(crate) fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
+
+        fn read(&self, lenient: bool) -> (Vec<Result<TraceEvent>>, Skipped) {
+            let mut reader = EventReader::open(self.path(), 1)
+                .unwrap()
+                .lenient(lenient);
+            let events = reader.by_ref().collect();
+            (events, reader.skipped())
+        }
+    }
+
+    impl TempTrace {
+        /// Events, as debug strings or errors, and skipped data of a
+        /// reader opened by opts.
+        fn read_opts(&self, opts: &crate::Options) -> (Vec<String>, Skipped) {
+            let mut reader = opts.open_reader(self.path()).unwrap();
+            let events = reader.by_ref()
+                .map(|evt| evt.map_or_else(|e| e.to_string(), |evt| format!("{:?}", evt)))
+                .collect();
+            (events, reader.skipped())
+        }
+    }
+
+    impl Drop for TempTrace {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn decode_head_and_payloads() {
+        let ax = [-100i64 as u64, 0x2000, 0x80000, 0, 0, 0, 0, SYS_OPENAT];
+        let data = record(IN, ax, &[(1, b"/etc/passwd\0")]);
+        let evt = parse_event(&mut Cursor::new(&data), 0, data.len(), 1).unwrap();
+        assert_eq!(evt.head.magic, LK_MAGIC);
+        assert_eq!(evt.head.totalsize as usize, data.len());
+        assert_eq!(evt.head.ax, ax);
+        assert_eq!(evt.head.usp, 0x3ffffffa40);
+        assert_eq!(evt.head.satp, 0x8000000000081234);
+        assert_eq!(evt.head.sscratch, 0x1000);
+        assert_eq!(evt.payloads.len(), 1);
+        assert_eq!(evt.payloads[0].index, 1);
+        assert_eq!(&evt.payloads[0].data[..], b"/etc/passwd\0");
+    }
+
+    #[test]
+    fn openat_mode_with_creat() {
+        let openat = |flags: u64| {
+            let ax = [-100i64 as u64, 0x2000, flags, 0o644, 0, 0, 0, SYS_OPENAT];
+            let data = record(IN, ax, &[(1, b"/tmp/x\0")]);
+            let evt = parse_event(&mut Cursor::new(&data), 0, data.len(), 1).unwrap();
+            evt.syscall().unwrap().args.join(", ")
+        };
+        assert_eq!(openat(0), "AT_FDCWD, \"/tmp/x\", O_RDONLY");
+        assert_eq!(openat(0o101), "AT_FDCWD, \"/tmp/x\", O_WRONLY|O_CREAT, 0644");
+        assert_eq!(openat(0o20200002), "AT_FDCWD, \"/tmp/x\", O_RDWR|O_TMPFILE, 0644");
+        assert_eq!(mode_name(0), "000");
+        assert_eq!(mode_name(0o40755), "040755");
+    }
+
+    #[test]
+    fn reject_bad_payload_magic() {
+        let mut data = record(OUT, [0, 0x2000, 64, 0, 0, 0, 0, SYS_READ], &[(1, b"abc")]);
+        data[TE_SIZE] = 0;
+        let err = parse_event(&mut Cursor::new(&data), 0, data.len(), 1).unwrap_err();
+        assert!(matches!(err, TraceError::BadPayloadMagic { offset, .. } if offset == TE_SIZE as u64));
+    }
+
+    #[test]
+    fn skip_bad_payload_magic() {
+        let mut bad = record(OUT, [0, 0x2000, 64, 0, 0, 0, 0, SYS_READ], &[(1, b"abc")]);
+        bad[TE_SIZE + 1] = 0;
+        let trace = TempTrace::new("payload", &[getpid(IN), bad.clone(), getpid(OUT)].concat());
+
+        let (events, _) = trace.read(false);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(events[1], Err(TraceError::BadPayloadMagic { .. })));
+
+        let (events, skipped) = trace.read(true);
+        let events = events.into_iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].head.inout, OUT);
+        assert_eq!(skipped.bytes, bad.len());
+        assert_eq!(skipped.events, 1);
+    }
+
+    #[test]
+    fn skip_truncated_tail() {
+        let tail = getpid(IN);
+        let data = [getpid(IN), getpid(OUT), tail[..TE_SIZE / 2].to_vec()].concat();
+        let trace = TempTrace::new("tail", &data);
+
+        let (events, _) = trace.read(false);
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[2], Err(TraceError::Truncated { left, .. }) if left == TE_SIZE / 2));
+
+        let (events, skipped) = trace.read(true);
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|evt| evt.is_ok()));
+        assert_eq!(skipped.bytes, TE_SIZE / 2);
+        assert_eq!(skipped.events, 1);
+    }
+
+    #[test]
+    fn skip_zeroed_gap() {
+        let data = [getpid(IN), vec![0; 300], getpid(OUT)].concat();
+        let trace = TempTrace::new("gap", &data);
+
+        let (events, _) = trace.read(false);
+        assert!(matches!(events[1], Err(TraceError::BadMagic { offset, magic: 0 }) if offset == TE_SIZE as u64));
+
+        let (events, skipped) = trace.read(true);
+        let events = events.into_iter().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(events.iter().map(|evt| evt.seq).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(events[1].offset, (TE_SIZE + 300) as u64);
+        assert_eq!(skipped.bytes, 300);
+        assert_eq!(skipped.events, 1);
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let mut bad_magic = record(OUT, [0, 0x2000, 64, 0, 0, 0, 0, SYS_READ], &[(1, b"abc")]);
+        bad_magic[TE_SIZE] = 0;
+        // Damaged payload with a whole event in it, where resync lands
+        let read = record(OUT, [0, 0x2000, 64, 0, 0, 0, 0, SYS_READ], &[(1, &getpid(IN))]);
+        let mut nested = read.clone();
+        nested[TE_SIZE + 4] = 0xff;
+        let mut data = vec![];
+        for i in 0..40 {
+            data.extend(getpid(if i % 2 == 0 { IN } else { OUT }));
+            match i {
+                5 => data.extend(vec![0; 300]),
+                12 => data.extend(&bad_magic),
+                20 => data.extend(&nested),
+                // Chunks may start at the head in its payload
+                _ if i % 7 == 3 => data.extend(&read),
+                _ => (),
+            }
+        }
+        data.extend(&getpid(IN)[..TE_SIZE / 2]);
+        let trace = TempTrace::new("parallel", &data);
+        let opts = crate::Options { level: 1, lenient: true, jobs: 4, ..Default::default() };
+        let (events, skipped) = trace.read_opts(&opts);
+        // The event nested in a damaged payload is read too
+        assert_eq!(events.len(), 47);
+        assert_eq!(skipped.events, 4);
+
+        for lenient in [false, true] {
+            for events in [None, Some(10..30)] {
+                let select = Selection { events, ..Selection::default() };
+                let opts = crate::Options { level: 1, lenient, select, ..Default::default() };
+                let (seq_events, seq_skipped) = trace.read_opts(&opts);
+                for opts in [crate::Options { jobs: 4, ..opts.clone() }, crate::Options { mmap: true, ..opts }] {
+                    let (par_events, par_skipped) = trace.read_opts(&opts);
+                    assert_eq!(par_events, seq_events);
+                    assert_eq!((par_skipped.bytes, par_skipped.events), (seq_skipped.bytes, seq_skipped.events));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mapped_payloads_left_in_map() {
+        let ax = [-100i64 as u64, 0x2000, 0x80000, 0, 0, 0, 0, SYS_OPENAT];
+        let trace = TempTrace::new("mapped", &record(IN, ax, &[(1, b"/etc/passwd\0")]));
+        let opts = crate::Options { level: 1, mmap: true, ..Default::default() };
+        let evt = opts.open_reader(trace.path()).unwrap().next().unwrap().unwrap();
+        assert!(matches!(evt.payloads[0].data, PayloadData::Mapped(_)));
+        assert_eq!(&evt.payloads[0].data[..], b"/etc/passwd\0");
+    }
 }
@@ -0,0 +1,149 @@
+//! Flags of open and *at syscalls, of asm-generic as riscv64 uses.
+
+/// Mask of access mode
+pub const O_ACCMODE: u64 = 0o3;
+pub const O_RDONLY: u64 = 0o0;
+pub const O_WRONLY: u64 = 0o1;
+pub const O_RDWR: u64 = 0o2;
+pub const O_CREAT: u64 = 0o100;
+pub const O_EXCL: u64 = 0o200;
+pub const O_NOCTTY: u64 = 0o400;
+pub const O_TRUNC: u64 = 0o1000;
+pub const O_APPEND: u64 = 0o2000;
+pub const O_NONBLOCK: u64 = 0o4000;
+pub const O_DSYNC: u64 = 0o10000;
+pub const FASYNC: u64 = 0o20000;
+pub const O_DIRECT: u64 = 0o40000;
+pub const O_LARGEFILE: u64 = 0o100000;
+pub const O_DIRECTORY: u64 = 0o200000;
+pub const O_NOFOLLOW: u64 = 0o400000;
+pub const O_NOATIME: u64 = 0o1000000;
+pub const O_CLOEXEC: u64 = 0o2000000;
+const __O_SYNC: u64 = 0o4000000;
+pub const O_SYNC: u64 = __O_SYNC | O_DSYNC;
+pub const O_PATH: u64 = 0o10000000;
+const __O_TMPFILE: u64 = 0o20000000;
+pub const O_TMPFILE: u64 = __O_TMPFILE | O_DIRECTORY;
+
+/// Duplicate fd to the lowest free fd at least arg
+pub const F_DUPFD: u64 = 0;
+/// Get fd flags
+pub const F_GETFD: u64 = 1;
+/// Set fd flags
+pub const F_SETFD: u64 = 2;
+/// As F_DUPFD, and set FD_CLOEXEC on the new fd
+pub const F_DUPFD_CLOEXEC: u64 = 1030;
+/// Fd flag, close fd on execve
+pub const FD_CLOEXEC: u64 = 1;
+
+/// Test for existence of file
+pub const F_OK: u64 = 0;
+/// Test for execute permission
+pub const X_OK: u64 = 1;
+/// Test for write permission
+pub const W_OK: u64 = 2;
+/// Test for read permission
+pub const R_OK: u64 = 4;
+
+/// Special value of dirfd, for the current working directory
+pub const AT_FDCWD: i32 = -100;
+/// Do not follow symbolic links
+pub const AT_SYMLINK_NOFOLLOW: u64 = 0x100;
+/// Remove directory instead of unlinking file
+pub const AT_REMOVEDIR: u64 = 0x200;
+/// Follow symbolic links
+pub const AT_SYMLINK_FOLLOW: u64 = 0x400;
+/// Suppress terminal automount traversal
+pub const AT_NO_AUTOMOUNT: u64 = 0x800;
+/// Allow empty relative pathname
+pub const AT_EMPTY_PATH: u64 = 0x1000;
+
+/// Open flags other than access mode. Flags of several bits come
+/// before the bits they contain.
+const OPEN_FLAGS: [(u64, &str); 19] = [
+    (O_TMPFILE, "O_TMPFILE"),
+    (O_SYNC, "O_SYNC"),
+    (O_CREAT, "O_CREAT"),
+    (O_EXCL, "O_EXCL"),
+    (O_NOCTTY, "O_NOCTTY"),
+    (O_TRUNC, "O_TRUNC"),
+    (O_APPEND, "O_APPEND"),
+    (O_NONBLOCK, "O_NONBLOCK"),
+    (O_DSYNC, "O_DSYNC"),
+    (FASYNC, "FASYNC"),
+    (O_DIRECT, "O_DIRECT"),
+    (O_LARGEFILE, "O_LARGEFILE"),
+    (O_DIRECTORY, "O_DIRECTORY"),
+    (O_NOFOLLOW, "O_NOFOLLOW"),
+    (O_NOATIME, "O_NOATIME"),
+    (O_CLOEXEC, "O_CLOEXEC"),
+    (__O_SYNC, "__O_SYNC"),
+    (O_PATH, "O_PATH"),
+    (__O_TMPFILE, "__O_TMPFILE"),
+];
+
+const AT_FLAGS: [(u64, &str); 5] = [
+    (AT_SYMLINK_NOFOLLOW, "AT_SYMLINK_NOFOLLOW"),
+    (AT_REMOVEDIR, "AT_REMOVEDIR"),
+    (AT_SYMLINK_FOLLOW, "AT_SYMLINK_FOLLOW"),
+    (AT_NO_AUTOMOUNT, "AT_NO_AUTOMOUNT"),
+    (AT_EMPTY_PATH, "AT_EMPTY_PATH"),
+];
+
+/// Names of flags in table which are set, and unknown bits in hex.
+fn names_of(flags: u64, table: &[(u64, &str)], names: &mut Vec<String>) {
+    let mut rest = flags;
+    for (flag, name) in table {
+        if rest & flag == *flag {
+            names.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 {
+        names.push(format!("{:#x}", rest));
+    }
+}
+
+/// generate open flags name, e.g. `O_RDONLY|O_CLOEXEC`
+pub fn open_flags_name(flags: u64) -> String {
+    let mut names: Vec<String> = vec![];
+    match flags & O_ACCMODE {
+        O_RDONLY => names.push("O_RDONLY".to_string()),
+        O_WRONLY => names.push("O_WRONLY".to_string()),
+        O_RDWR => names.push("O_RDWR".to_string()),
+        mode => names.push(format!("{:#o}", mode)),
+    }
+    names_of(flags & !O_ACCMODE, &OPEN_FLAGS, &mut names);
+    names.join("|")
+}
+
+/// generate file mode name, in octal as chmod takes it, e.g. `0644`
+pub fn mode_name(mode: u64) -> String {
+    format!("0{:02o}", mode)
+}
+
+/// Whether openat takes its mode argument, which is only with O_CREAT
+/// or O_TMPFILE, so strace leaves it out otherwise.
+pub fn open_has_mode(flags: u64) -> bool {
+    flags & (O_CREAT | __O_TMPFILE) != 0
+}
+
+/// generate access mode name of faccessat, e.g. `R_OK|W_OK`
+pub fn access_mode_name(mode: u64) -> String {
+    if mode == F_OK {
+        return String::from("F_OK");
+    }
+    let mut names: Vec<String> = vec![];
+    names_of(mode, &[(R_OK, "R_OK"), (W_OK, "W_OK"), (X_OK, "X_OK")], &mut names);
+    names.join("|")
+}
+
+/// generate AT_* flags name of *at syscalls
+pub fn at_flags_name(flags: u64) -> String {
+    if flags == 0 {
+        return String::from("0");
+    }
+    let mut names: Vec<String> = vec![];
+    names_of(flags, &AT_FLAGS, &mut names);
+    names.join("|")
+}
@@ -0,0 +1,318 @@
+//! Tables of file descriptors, rebuilt from syscalls which open, dup
+//! and close them.
+//!
+//! Each task has a table, which it shares with tasks cloned with
+//! CLONE_FILES, or else gets a copy of at clone. Execve unshares the
+//! table, and closes fds with FD_CLOEXEC. A table is dropped when the
+//! last task using it exits. Fds which were opened before trace starts,
+//! e.g. stdio, have no path.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use crate::clone::CLONE_FILES;
+use crate::errno::is_error;
+use crate::event::TraceEvent;
+use crate::fcntl::*;
+use crate::signature::{signature, Param};
+use crate::sysno::*;
+
+/// File which an fd refers to.
+#[derive(Clone, Debug)]
+pub struct OpenFile {
+    /// Path as opened, relative to dirfd if that isn't known
+    pub path: String,
+    /// Closed on execve
+    pub cloexec: bool,
+}
+
+type FdTable = HashMap<i32, OpenFile>;
+
+/// Table with the number of its users: tasks, and clone requests which
+/// hold it for their children.
+struct Shared {
+    files: FdTable,
+    users: usize,
+}
+
+/// Fd tables of tasks, fed with requests and replies in trace order by
+/// [`crate::level1::Grouper`].
+#[derive(Default)]
+pub struct FdTables {
+    tables: HashMap<usize, Shared>,
+    /// Id of next new table
+    next: usize,
+    /// Table of each task, by id
+    tasks: HashMap<u64, usize>,
+}
+
+fn c_str(evt: &TraceEvent, index: usize) -> Option<String> {
+    let payload = evt.payload(index)?;
+    let s = CStr::from_bytes_until_nul(&payload.data).ok()?;
+    Some(s.to_string_lossy().into_owned())
+}
+
+impl FdTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// New table with one user.
+    fn add(&mut self, files: FdTable) -> usize {
+        let id = self.next;
+        self.next += 1;
+        self.tables.insert(id, Shared { files, users: 1 });
+        id
+    }
+
+    /// Id of table of task. A task seen first in trace gets an empty
+    /// one.
+    fn table_of(&mut self, tid: u64) -> usize {
+        match self.tasks.get(&tid) {
+            Some(id) => *id,
+            None => {
+                let id = self.add(FdTable::new());
+                self.tasks.insert(tid, id);
+                id
+            },
+        }
+    }
+
+    fn table_mut(&mut self, tid: u64) -> &mut FdTable {
+        let id = self.table_of(tid);
+        &mut self.tables.get_mut(&id).unwrap().files
+    }
+
+    /// File of fd in task, if it's known.
+    pub fn get(&self, tid: u64, fd: i32) -> Option<&OpenFile> {
+        self.tables[self.tasks.get(&tid)?].files.get(&fd)
+    }
+
+    /// Hold the table of a child which parent clones with flags: the
+    /// parent's own table with CLONE_FILES, or else a copy of it. It's
+    /// taken at the clone request, as the parent is in clone until its
+    /// reply, which may only come after the child runs, e.g. by vfork.
+    /// Returns the id of table for [`Self::attach`] or [`Self::release`].
+    pub fn hold(&mut self, parent: u64, flags: u64) -> usize {
+        let id = self.table_of(parent);
+        if flags & CLONE_FILES == 0 {
+            let files = self.tables[&id].files.clone();
+            return self.add(files);
+        }
+        self.tables.get_mut(&id).unwrap().users += 1;
+        id
+    }
+
+    /// Child task starts with the table held for it by clone.
+    pub fn attach(&mut self, child: u64, id: usize) {
+        if let Some(old) = self.tasks.insert(child, id) {
+            self.release(old);
+        }
+    }
+
+    /// Drop a user of table, e.g. a clone request which failed. The
+    /// table is dropped with its last user.
+    pub fn release(&mut self, id: usize) {
+        if let Some(shared) = self.tables.get_mut(&id) {
+            shared.users -= 1;
+            if shared.users == 0 {
+                self.tables.remove(&id);
+            }
+        }
+    }
+
+    /// Task exits, and no longer uses its table.
+    pub fn exit(&mut self, tid: u64) {
+        if let Some(id) = self.tasks.remove(&tid) {
+            self.release(id);
+        }
+    }
+
+    /// Record paths of fd arguments of request, as the table of task
+    /// has them before the syscall.
+    pub fn request(&mut self, tid: u64, evt: &mut TraceEvent) {
+        let table = self.table_mut(tid);
+        for (index, arg) in signature(evt.head.ax[7]).args.iter().enumerate() {
+            let fd = evt.head.ax[index] as i32;
+            if !matches!(arg.param, Param::Fd | Param::Dirfd) {
+                continue;
+            }
+            if let Some(file) = table.get(&fd) {
+                evt.fd_paths.push((index, file.path.clone()));
+            }
+        }
+    }
+
+    /// Update table of task by a completed syscall.
+    pub fn reply(&mut self, tid: u64, evt: &TraceEvent) {
+        let ax = evt.head.ax;
+        // Fd is freed even if close fails
+        if is_error(evt.result) && ax[7] != SYS_CLOSE {
+            return;
+        }
+        let fd = ax[0] as i32;
+        match ax[7] {
+            SYS_OPENAT => {
+                let path = c_str(evt, 1).map(|path| match evt.fd_paths.first() {
+                    // Dirfd of relative path is known
+                    Some((0, dir)) if !path.starts_with('/') => {
+                        format!("{}/{}", dir.trim_end_matches('/'), path)
+                    },
+                    _ => path,
+                });
+                let cloexec = ax[2] & O_CLOEXEC != 0;
+                self.set(tid, evt.result as i32, path.map(|path| OpenFile { path, cloexec }));
+            },
+            SYS_DUP => self.dup(tid, fd, evt.result as i32, false),
+            SYS_DUP3 => self.dup(tid, fd, ax[1] as i32, ax[2] & O_CLOEXEC != 0),
+            SYS_FCNTL => match ax[1] {
+                F_DUPFD => self.dup(tid, fd, evt.result as i32, false),
+                F_DUPFD_CLOEXEC => self.dup(tid, fd, evt.result as i32, true),
+                F_SETFD => {
+                    if let Some(file) = self.table_mut(tid).get_mut(&fd) {
+                        file.cloexec = ax[2] & FD_CLOEXEC != 0;
+                    }
+                },
+                _ => (),
+            },
+            SYS_CLOSE => self.set(tid, fd, None),
+            SYS_EXECVE => {
+                let mut files = self.table_mut(tid).clone();
+                files.retain(|_, file| !file.cloexec);
+                let id = self.add(files);
+                self.attach(tid, id);
+            },
+            _ => (),
+        }
+    }
+
+    /// Newfd refers to the file of oldfd, which may be unknown.
+    fn dup(&mut self, tid: u64, oldfd: i32, newfd: i32, cloexec: bool) {
+        let file = self.table_mut(tid).get(&oldfd).map(|file| OpenFile {
+            path: file.path.clone(),
+            cloexec,
+        });
+        self.set(tid, newfd, file);
+    }
+
+    fn set(&mut self, tid: u64, fd: i32, file: Option<OpenFile>) {
+        let table = self.table_mut(tid);
+        match file {
+            Some(file) => table.insert(fd, file),
+            None => table.remove(&fd),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clone::CLONE_VM;
+    use crate::event::TracePayload;
+    use crate::IN;
+
+    /// Completed syscall of args and result.
+    fn syscall(tables: &mut FdTables, tid: u64, ax: [u64; 8], result: i64, path: Option<&str>) {
+        let mut evt = TraceEvent::default();
+        evt.head.ax = ax;
+        evt.result = result;
+        if let Some(path) = path {
+            let mut data = path.as_bytes().to_vec();
+            data.push(0);
+            evt.payloads.push(TracePayload { inout: IN, index: 1, data: data.into() });
+        }
+        tables.request(tid, &mut evt);
+        tables.reply(tid, &evt);
+    }
+
+    fn open(tables: &mut FdTables, tid: u64, path: &str, flags: u64, fd: i32) {
+        let ax = [AT_FDCWD as u64, 0, flags, 0, 0, 0, 0, SYS_OPENAT];
+        syscall(tables, tid, ax, fd as i64, Some(path));
+    }
+
+    fn path(tables: &FdTables, tid: u64, fd: i32) -> Option<&str> {
+        tables.get(tid, fd).map(|file| file.path.as_str())
+    }
+
+    #[test]
+    fn dup_and_close() {
+        let mut tables = FdTables::new();
+        open(&mut tables, 1, "/a", 0, 3);
+        syscall(&mut tables, 1, [3, 0, 0, 0, 0, 0, 0, SYS_DUP], 4, None);
+        syscall(&mut tables, 1, [3, 7, O_CLOEXEC, 0, 0, 0, 0, SYS_DUP3], 7, None);
+        syscall(&mut tables, 1, [3, F_DUPFD_CLOEXEC, 10, 0, 0, 0, 0, SYS_FCNTL], 10, None);
+        assert_eq!(path(&tables, 1, 4), Some("/a"));
+        assert!(!tables.get(1, 4).unwrap().cloexec);
+        assert!(tables.get(1, 7).unwrap().cloexec);
+        assert!(tables.get(1, 10).unwrap().cloexec);
+
+        syscall(&mut tables, 1, [3, 0, 0, 0, 0, 0, 0, SYS_CLOSE], 0, None);
+        assert_eq!(path(&tables, 1, 3), None);
+        assert_eq!(path(&tables, 1, 4), Some("/a"));
+        // Fd is freed even if close fails
+        syscall(&mut tables, 1, [4, 0, 0, 0, 0, 0, 0, SYS_CLOSE], -4, None);
+        assert_eq!(path(&tables, 1, 4), None);
+        // Failed dup changes nothing
+        syscall(&mut tables, 1, [7, 9, 0, 0, 0, 0, 0, SYS_DUP3], -9, None);
+        assert_eq!(path(&tables, 1, 9), None);
+    }
+
+    #[test]
+    fn cloexec_closed_on_execve() {
+        let mut tables = FdTables::new();
+        open(&mut tables, 1, "/a", 0, 3);
+        open(&mut tables, 1, "/b", O_CLOEXEC, 4);
+        open(&mut tables, 1, "/c", 0, 5);
+        syscall(&mut tables, 1, [5, F_SETFD, FD_CLOEXEC, 0, 0, 0, 0, SYS_FCNTL], 0, None);
+        let shared = tables.hold(1, CLONE_VM | CLONE_FILES);
+        tables.attach(2, shared);
+
+        syscall(&mut tables, 1, [0, 0, 0, 0, 0, 0, 0, SYS_EXECVE], 0, None);
+        assert_eq!(path(&tables, 1, 3), Some("/a"));
+        assert_eq!(path(&tables, 1, 4), None);
+        assert_eq!(path(&tables, 1, 5), None);
+        // Execve unshares the table
+        assert_eq!(path(&tables, 2, 4), Some("/b"));
+        open(&mut tables, 1, "/d", 0, 6);
+        assert_eq!(path(&tables, 2, 6), None);
+    }
+
+    #[test]
+    fn clone_shares_or_copies() {
+        let mut tables = FdTables::new();
+        open(&mut tables, 1, "/a", 0, 3);
+        let shared = tables.hold(1, CLONE_VM | CLONE_FILES);
+        let copied = tables.hold(1, 0);
+        // Parent opens after clone, before child starts
+        open(&mut tables, 1, "/b", 0, 4);
+        tables.attach(2, shared);
+        tables.attach(3, copied);
+        assert_eq!(path(&tables, 2, 3), Some("/a"));
+        assert_eq!(path(&tables, 3, 3), Some("/a"));
+        assert_eq!(path(&tables, 2, 4), Some("/b"));
+        assert_eq!(path(&tables, 3, 4), None);
+
+        syscall(&mut tables, 2, [3, 0, 0, 0, 0, 0, 0, SYS_CLOSE], 0, None);
+        assert_eq!(path(&tables, 1, 3), None);
+        assert_eq!(path(&tables, 3, 3), Some("/a"));
+    }
+
+    #[test]
+    fn tables_freed_with_last_user() {
+        let mut tables = FdTables::new();
+        open(&mut tables, 1, "/a", 0, 3);
+        let shared = tables.hold(1, CLONE_FILES);
+        let failed = tables.hold(1, 0);
+        assert_eq!(tables.tables.len(), 2);
+        tables.release(failed);
+        assert_eq!(tables.tables.len(), 1);
+
+        tables.exit(1);
+        assert_eq!(tables.tables.len(), 1);
+        tables.attach(2, shared);
+        assert_eq!(path(&tables, 2, 3), Some("/a"));
+        syscall(&mut tables, 2, [0, 0, 0, 0, 0, 0, 0, SYS_EXECVE], 0, None);
+        assert_eq!(tables.tables.len(), 1);
+        tables.exit(2);
+        assert!(tables.tables.is_empty());
+    }
+}
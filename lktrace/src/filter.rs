@@ -0,0 +1,181 @@
+//! Filters of syscalls to print, by name, result and arguments.
+//!
+//! Filters are applied to events after grouping, so the events left
+//! are still grouped by task. Signal deliveries aren't syscalls, and
+//! they are kept, as strace does. Tasks and address spaces are selected
+//! before reading, see [`crate::index::Selection`], and so are syscalls
+//! of `trace`, see [`crate::Options::selection`].
+
+use std::collections::HashSet;
+use crate::OUT;
+use crate::errno::{errno_no, is_error, MAX_ERRNO};
+use crate::event::{SigStage, TraceEvent};
+use crate::sysno::syscall_no;
+
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Numbers of syscalls to keep, by name as `-e trace=openat,execve`
+    pub trace: Option<HashSet<u64>>,
+    /// Only failed syscalls
+    pub failed_only: bool,
+    /// Only syscalls failed with errno, e.g. 2 for ENOENT
+    pub errno: Option<i64>,
+    /// Only syscalls with a path argument, or an fd of a known path,
+    /// containing it
+    pub path_contains: Option<String>,
+}
+
+impl Filter {
+    /// Parse expression of `-e`. Only `trace=name,...` is supported,
+    /// and unknown names are errors as in strace.
+    pub fn parse_expr(&mut self, expr: &str) -> Result<(), String> {
+        let Some(names) = expr.strip_prefix("trace=") else {
+            return Err(format!("unsupported expression {}, expect trace=name,...", expr));
+        };
+        let sysnos = names
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(|name| syscall_no(name).ok_or_else(|| format!("invalid system call '{}'", name)))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.trace.get_or_insert_with(HashSet::new).extend(sysnos);
+        Ok(())
+    }
+
+    /// Parse errno of `--errno`, by name as ENOENT or by number as 2.
+    /// Unknown names are errors, as for `trace`.
+    pub fn parse_errno(&mut self, errno: &str) -> Result<(), String> {
+        let no = match errno.parse::<i64>() {
+            Ok(no) if (1..=MAX_ERRNO).contains(&no) => no,
+            Ok(_) => return Err(format!("invalid errno {}, expect 1..={}", errno, MAX_ERRNO)),
+            Err(_) => errno_no(errno).ok_or_else(|| format!("invalid errno '{}'", errno))?.into(),
+        };
+        self.errno = Some(no);
+        Ok(())
+    }
+
+    /// Whether all events are kept.
+    pub fn is_empty(&self) -> bool {
+        self.trace.is_none() && !self.failed_only && self.errno.is_none()
+            && self.path_contains.is_none()
+    }
+
+    /// Whether to keep an event of task after grouping.
+    pub fn matches(&self, evt: &TraceEvent) -> bool {
+        match evt.signal {
+            SigStage::Enter(_) => true,
+            // Syscall interrupted by signal has no result
+            SigStage::Exit(_) => self.matches_result(evt, None),
+            SigStage::Empty => self.matches_result(evt, Some(evt.result)),
+        }
+    }
+
+    /// Whether to keep a raw event at level 0. Only replies have results,
+    /// so requests are never kept if results are filtered.
+    pub fn matches_raw(&self, evt: &TraceEvent) -> bool {
+        let result = (evt.head.inout == OUT).then_some(evt.head.ax[0] as i64);
+        self.matches_result(evt, result)
+    }
+
+    fn matches_result(&self, evt: &TraceEvent, result: Option<i64>) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let failed = result.is_some_and(is_error);
+        if (self.failed_only && !failed)
+            || self.errno.is_some_and(|errno| result != Some(-errno))
+        {
+            return false;
+        }
+        if self.trace.as_ref().is_some_and(|trace| !trace.contains(&evt.head.ax[7])) {
+            return false;
+        }
+        self.path_contains.as_ref().is_none_or(|needle| {
+            evt.paths().any(|path| path.contains(needle.as_str()))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IN;
+    use crate::event::{TracePayload, USER_ECALL};
+    use crate::sysno::*;
+
+    /// Completed syscall, whose reply returns result.
+    fn event(sysno: u64, result: i64) -> TraceEvent {
+        let mut evt = TraceEvent { level: 1, result, ..Default::default() };
+        evt.head.inout = OUT;
+        evt.head.cause = USER_ECALL;
+        evt.head.ax[0] = result as u64;
+        evt.head.ax[7] = sysno;
+        evt
+    }
+
+    fn openat(path: &str, result: i64) -> TraceEvent {
+        let mut evt = event(SYS_OPENAT, result);
+        let data = [path.as_bytes(), &[0]].concat();
+        evt.payloads.push(TracePayload { inout: IN, index: 1, data: data.into() });
+        evt
+    }
+
+    #[test]
+    fn parse_exprs() {
+        let mut filter = Filter::default();
+        filter.parse_expr("trace=openat,read").unwrap();
+        filter.parse_expr("trace=close").unwrap();
+        assert_eq!(filter.trace, Some(HashSet::from([SYS_OPENAT, SYS_READ, SYS_CLOSE])));
+        assert_eq!(filter.parse_expr("trace=openat,nope"), Err("invalid system call 'nope'".into()));
+        assert!(filter.parse_expr("signal=all").is_err());
+    }
+
+    #[test]
+    fn parse_errnos() {
+        let mut filter = Filter::default();
+        filter.parse_errno("ENOENT").unwrap();
+        assert_eq!(filter.errno, Some(2));
+        filter.parse_errno("13").unwrap();
+        assert_eq!(filter.errno, Some(13));
+        assert_eq!(filter.parse_errno("ENOPE"), Err("invalid errno 'ENOPE'".into()));
+        assert!(filter.parse_errno("0").is_err());
+        assert!(filter.parse_errno("-2").is_err());
+        assert!(filter.parse_errno("4096").is_err());
+    }
+
+    #[test]
+    fn matches_events() {
+        let failed = openat("/nope", -2);
+        let denied = openat("/etc/shadow", -13);
+        let ok = openat("/proc/self/maps", 3);
+        let mut read = event(SYS_READ, 10);
+        read.fd_paths.push((0, "/proc/self/maps".into()));
+        let mut interrupted = event(SYS_READ, -4);
+        interrupted.signal = SigStage::Exit(10);
+        let signal = TraceEvent { signal: SigStage::Enter(10), ..Default::default() };
+        let events = [&failed, &denied, &ok, &read, &interrupted, &signal];
+        let kept = |filter: &Filter| events.iter().map(|evt| filter.matches(evt)).collect::<Vec<_>>();
+
+        assert_eq!(kept(&Filter::default()), [true; 6]);
+        let filter = Filter { failed_only: true, ..Default::default() };
+        assert_eq!(kept(&filter), [true, true, false, false, false, true]);
+        let filter = Filter { errno: Some(2), ..Default::default() };
+        assert_eq!(kept(&filter), [true, false, false, false, false, true]);
+        let filter = Filter { trace: Some(HashSet::from([SYS_READ])), ..Default::default() };
+        assert_eq!(kept(&filter), [false, false, false, true, true, true]);
+        let filter = Filter { path_contains: Some("/proc".into()), ..Default::default() };
+        assert_eq!(kept(&filter), [false, false, true, true, false, true]);
+        let filter = Filter { failed_only: true, path_contains: Some("/etc".into()), ..Default::default() };
+        assert_eq!(kept(&filter), [false, true, false, false, false, true]);
+    }
+
+    #[test]
+    fn matches_raw_replies() {
+        let filter = Filter { errno: Some(2), ..Default::default() };
+        let mut request = openat("/nope", 0);
+        request.head.inout = IN;
+        request.head.ax[0] = -2i64 as u64;
+        assert!(!filter.matches_raw(&request));
+        assert!(filter.matches_raw(&openat("/nope", -2)));
+        assert!(!filter.matches_raw(&openat("/nope", 3)));
+    }
+}
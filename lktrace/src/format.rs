@@ -0,0 +1,183 @@
+//! Output formats of analysis.
+//!
+//! Text is the format for people, as printed by `Display` of events.
+//! JSON Lines is for scripts: each line is an object whose `type` tells
+//! what it is, e.g. `syscall` for a completed syscall of a task.
+//! Strace prints completed syscalls as strace does, so that tools and
+//! habits for strace logs work on traces too.
+
+use std::fmt::{self, Display, Formatter, Write};
+use std::str::FromStr;
+use serde_json::{json, Value};
+use crate::OUT;
+use crate::errno::{errno_msg, errno_name, is_error};
+use crate::signature::{signature, Ret};
+use crate::sysno::{SYS_EXIT, SYS_EXIT_GROUP};
+use crate::event::{print_skipped, SigStage, Skipped, TraceEvent, TraceHead};
+use crate::signal::sig_name;
+
+/// Output format, see module docs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Text,
+    Jsonl,
+    Strace,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "jsonl" => Ok(Format::Jsonl),
+            "strace" => Ok(Format::Strace),
+            _ => Err(format!("unknown format {}, expect text, jsonl or strace", s)),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Jsonl => write!(f, "jsonl"),
+            Format::Strace => write!(f, "strace"),
+        }
+    }
+}
+
+/// Raw event at level 0.
+pub fn raw_json(head: &TraceHead, offset: u64) -> Value {
+    json!({
+        "type": "event",
+        "offset": offset,
+        "task": format!("{:#x}", head.sscratch),
+        "satp": format!("{:#x}", head.satp),
+        "inout": if head.inout == OUT { "out" } else { "in" },
+        "cause": head.cause,
+        "epc": format!("{:#x}", head.epc),
+        "sysno": head.ax[7],
+        "args": head.ax[..7].iter().map(|arg| format!("{:#x}", arg)).collect::<Vec<_>>(),
+        "usp": format!("{:#x}", head.usp),
+    })
+}
+
+/// Fields of syscalls which hold raw values of the traced machine, e.g.
+/// pointers, pids and satp, left out at level 2 so that runs of the same
+/// workload can be diffed. Args and ret are decoded with seq-names.
+const RAW_FIELDS: [&str; 5] = ["satp", "raw_args", "payloads", "result", "usp"];
+
+/// Event of task after grouping. Seq is its index in the task.
+pub fn event_json(task: &str, seq: usize, evt: &TraceEvent) -> Value {
+    let signal = match evt.signal {
+        SigStage::Empty => Value::Null,
+        SigStage::Enter(signo) => json!({ "stage": "enter", "signo": signo, "name": sig_name(signo) }),
+        SigStage::Exit(signo) => json!({ "stage": "exit", "signo": signo, "name": sig_name(signo) }),
+    };
+    // Signal delivery isn't a syscall, its head is empty
+    if let SigStage::Enter(_) = evt.signal {
+        return json!({ "type": "signal", "task": task, "seq": seq, "signal": signal });
+    }
+
+    let Some(call) = evt.syscall() else {
+        return json!({ "type": "event", "task": task, "seq": seq, "cause": evt.head.cause });
+    };
+    let failed = is_error(evt.result);
+    let errno = failed.then(|| errno_name(evt.result));
+    let errmsg = failed.then(|| errno_msg(evt.result));
+    let payloads = evt.payloads
+        .iter()
+        .map(|p| json!({
+            "index": p.index,
+            "inout": if p.inout == OUT { "out" } else { "in" },
+            "data": p.data.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+        }))
+        .collect::<Vec<_>>();
+    let mut value = json!({
+        "type": "syscall",
+        "task": task,
+        "seq": seq,
+        "satp": format!("{:#x}", evt.head.satp),
+        "sysno": evt.head.ax[7],
+        "name": call.name,
+        "raw_args": evt.head.ax[..call.args.len()].iter().map(|arg| format!("{:#x}", arg)).collect::<Vec<_>>(),
+        "args": call.args,
+        "arg_names": call.arg_names,
+        "payloads": payloads,
+        "result": evt.result,
+        "ret": call.ret,
+        "errno": errno,
+        "errmsg": errmsg,
+        "signal": signal,
+        "usp": format!("{:#x}", evt.head.usp),
+    });
+    if evt.level >= 2 {
+        let fields = value.as_object_mut().unwrap();
+        RAW_FIELDS.iter().for_each(|field| {
+            fields.remove(*field);
+        });
+    }
+    value
+}
+
+/// Event of task after grouping in strace format, which is one line
+/// `task name(args) = ret`, or `--- SIGxxx ---` for signal delivery.
+/// A syscall interrupted by the signal follows with result `?`.
+pub fn strace_lines(out: &mut String, task: &str, evt: &TraceEvent) {
+    if let SigStage::Enter(signo) = evt.signal {
+        let _ = writeln!(out, "{} --- {} ---", task, sig_name(signo));
+        return;
+    }
+
+    let Some(call) = evt.syscall() else {
+        return;
+    };
+    let sysno = evt.head.ax[7];
+    // exit and exit_group never return, and a syscall interrupted by
+    // signal has no result
+    let exits = sysno == SYS_EXIT || sysno == SYS_EXIT_GROUP;
+    let ret = if exits || matches!(evt.signal, SigStage::Exit(_)) {
+        "?".to_string()
+    } else if is_error(evt.result) {
+        format!("-1 {} ({})", errno_name(evt.result), errno_msg(evt.result))
+    } else if signature(sysno).ret == Ret::Addr {
+        format!("{:#x}", evt.result)
+    } else if signature(sysno).ret == Ret::Pid {
+        // pid or tid replaced with its seq-name at level 2
+        evt.names.pid(evt.result).unwrap_or_else(|| evt.result.to_string())
+    } else {
+        evt.result.to_string()
+    };
+    let _ = writeln!(out, "{} {}({}) = {}", task, call.name, call.args.join(", "), ret);
+    if exits {
+        let _ = writeln!(out, "{} +++ exited with {} +++", task, evt.head.ax[0] as i32);
+    }
+}
+
+/// Tasks in order of their first event.
+pub fn tasks_json(tasks: &[String]) -> Value {
+    json!({ "type": "tasks", "tasks": tasks })
+}
+
+/// Report data skipped by a lenient reader in format, if there is any.
+pub fn report_skipped(format: Format, skipped: Skipped, dropped: usize) {
+    match format {
+        Format::Text | Format::Strace => print_skipped(skipped, dropped),
+        Format::Jsonl if skipped.bytes > 0 || dropped > 0 => {
+            println!("{}", skipped_json(skipped, dropped));
+        },
+        Format::Jsonl => (),
+    }
+}
+
+/// Data skipped by a lenient reader, see [`print_skipped`].
+pub fn skipped_json(skipped: Skipped, dropped: usize) -> Value {
+    json!({
+        "type": "skipped",
+        "bytes": skipped.bytes,
+        "events": skipped.events,
+        "dropped": dropped,
+    })
+}
@@ -0,0 +1,322 @@
+//! Sidecar index of trace data for random access.
+//!
+//! The index is written next to the data file as `<file>.idx`. It
+//! records the offset of every event together with its task (sscratch),
+//! address space (satp) and syscall number, so that readers can seek to
+//! the events they need instead of parsing the file from the start.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use crate::bytes::LeReader;
+use crate::error::{Result, TraceError};
+use crate::event::TraceHead;
+use crate::mapped::MappedTrace;
+
+const INDEX_MAGIC: &[u8; 8] = b"LKTRIDX\0";
+const INDEX_VERSION: u32 = 1;
+/// Size of index head on disk
+const HEAD_SIZE: usize = 32;
+/// Size of IndexEntry on disk
+const ENTRY_SIZE: usize = 32;
+
+/// Events to read. Events are numbered in file order from 0, requests
+/// and replies separately, as printed by level 0.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// Task (sscratch)
+    pub task: Option<u64>,
+    /// Address space (satp)
+    pub satp: Option<u64>,
+    /// Window of event numbers
+    pub events: Option<Range<u64>>,
+    /// Syscall numbers
+    pub sysnos: Option<HashSet<u64>>,
+}
+
+impl Selection {
+    /// Whether all events are selected.
+    pub fn is_all(&self) -> bool {
+        self.task.is_none() && self.satp.is_none() && self.events.is_none()
+            && self.sysnos.is_none()
+    }
+
+    pub fn matches(&self, seq: u64, head: &TraceHead) -> bool {
+        self.matches_fields(seq, head.sscratch, head.satp, head.ax[7])
+    }
+
+    /// Whether no events at or after seq can be selected.
+    pub fn after(&self, seq: u64) -> bool {
+        self.events.as_ref().is_some_and(|events| seq >= events.end)
+    }
+
+    pub(crate) fn matches_fields(&self, seq: u64, sscratch: u64, satp: u64, sysno: u64) -> bool {
+        self.task.is_none_or(|task| task == sscratch)
+            && self.satp.is_none_or(|s| s == satp)
+            && self.events.as_ref().is_none_or(|events| events.contains(&seq))
+            && self.sysnos.as_ref().is_none_or(|sysnos| sysnos.contains(&sysno))
+    }
+}
+
+/// Where an event is in data file, and what it is about.
+#[derive(Clone, Copy, Debug)]
+pub struct IndexEntry {
+    pub offset: u64,
+    pub sscratch: u64,
+    pub satp: u64,
+    pub sysno: u32,
+    pub inout: u32,
+}
+
+/// Index of all events in a data file, in file order.
+pub struct TraceIndex {
+    /// Size of data file when the index was built
+    pub data_size: u64,
+    pub entries: Vec<IndexEntry>,
+    /// (sscratch, event number) of entries, sorted
+    task_keys: Vec<(u64, u64)>,
+    /// (satp, event number) of entries, sorted
+    satp_keys: Vec<(u64, u64)>,
+    /// (sysno, event number) of entries, sorted
+    sysno_keys: Vec<(u64, u64)>,
+}
+
+impl TraceIndex {
+    fn new(data_size: u64, entries: Vec<IndexEntry>) -> Self {
+        let keys = |key: fn(&IndexEntry) -> u64| {
+            let mut keys: Vec<_> = entries.iter().zip(0..).map(|(e, seq)| (key(e), seq)).collect();
+            keys.sort_unstable();
+            keys
+        };
+        let task_keys = keys(|e| e.sscratch);
+        let satp_keys = keys(|e| e.satp);
+        let sysno_keys = keys(|e| e.sysno as u64);
+        Self { data_size, entries, task_keys, satp_keys, sysno_keys }
+    }
+
+    /// Path of index for data file.
+    pub fn path_for(data_path: &str) -> String {
+        format!("{}.idx", data_path)
+    }
+
+    /// Build index by scanning data file. A lenient build skips damaged
+    /// events, as a lenient reader does.
+    pub fn build(data_path: &str, lenient: bool) -> Result<Self> {
+        let trace = MappedTrace::open(data_path)?;
+        let data_size = trace.data().len() as u64;
+        let mut entries = vec![];
+        let mut offset = 0;
+        while offset < data_size {
+            let view = trace.view_at(offset).and_then(|view| {
+                view.payloads()?;
+                Ok(view)
+            });
+            match view {
+                Ok(view) => {
+                    entries.push(IndexEntry {
+                        offset,
+                        sscratch: view.head.sscratch,
+                        satp: view.head.satp,
+                        sysno: view.head.ax[7] as u32,
+                        inout: view.head.inout as u32,
+                    });
+                    offset += view.bytes().len() as u64;
+                },
+                Err(e) if lenient => {
+                    warn!("{}, skip it", e);
+                    offset = trace.find_head(offset + 1).unwrap_or(data_size);
+                },
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(Self::new(data_size, entries))
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        w.write_all(INDEX_MAGIC)?;
+        w.write_all(&INDEX_VERSION.to_le_bytes())?;
+        w.write_all(&0u32.to_le_bytes())?;
+        w.write_all(&self.data_size.to_le_bytes())?;
+        w.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for e in &self.entries {
+            w.write_all(&e.offset.to_le_bytes())?;
+            w.write_all(&e.sscratch.to_le_bytes())?;
+            w.write_all(&e.satp.to_le_bytes())?;
+            w.write_all(&e.sysno.to_le_bytes())?;
+            w.write_all(&e.inout.to_le_bytes())?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let buf = fs::read(path)?;
+        let bad = |reason| TraceError::BadIndex { path: path.to_string(), reason };
+        if buf.len() < HEAD_SIZE || &buf[..INDEX_MAGIC.len()] != INDEX_MAGIC {
+            return Err(bad("not an index"));
+        }
+        let mut r = LeReader::new(&buf[INDEX_MAGIC.len()..]);
+        if r.u32() != INDEX_VERSION {
+            return Err(bad("unsupported version"));
+        }
+        let data_size = r.skip(4).u64();
+        let count = r.u64() as usize;
+        if (buf.len() - HEAD_SIZE) / ENTRY_SIZE != count {
+            return Err(bad("size doesn't match number of entries"));
+        }
+        let mut r = LeReader::new(&buf[HEAD_SIZE..]);
+        let entries: Vec<_> = (0..count)
+            .map(|_| IndexEntry {
+                offset: r.u64(),
+                sscratch: r.u64(),
+                satp: r.u64(),
+                sysno: r.u32(),
+                inout: r.u32(),
+            })
+            .collect();
+        if entries.iter().any(|e| e.offset >= data_size) {
+            return Err(bad("offset beyond end of data"));
+        }
+        Ok(Self::new(data_size, entries))
+    }
+
+    /// Load index of data file if there is one and it's up to date,
+    /// i.e. the data file hasn't changed size since it was built.
+    pub fn load_for(data_path: &str) -> Result<Option<Self>> {
+        let path = Self::path_for(data_path);
+        if fs::metadata(&path).is_err() {
+            return Ok(None);
+        }
+        let index = Self::load(&path)?;
+        if index.data_size != fs::metadata(data_path)?.len() {
+            warn!("index {} is out of date, ignore it", path);
+            return Ok(None);
+        }
+        Ok(Some(index))
+    }
+
+    /// Numbers and offsets of selected events, in file order.
+    pub fn lookup(&self, select: &Selection) -> Vec<(u64, u64)> {
+        let len = self.entries.len() as u64;
+        let events = select.events.clone().unwrap_or(0..len);
+        let events = events.start.min(len)..events.end.max(events.start).min(len);
+        let seqs: Vec<u64> = match (select.task, select.satp, &select.sysnos) {
+            (Some(task), _, _) => keys_in(&self.task_keys, task, &events),
+            (None, Some(satp), _) => keys_in(&self.satp_keys, satp, &events),
+            (None, None, Some(sysnos)) => {
+                let mut seqs: Vec<u64> = sysnos.iter()
+                    .flat_map(|sysno| keys_in(&self.sysno_keys, *sysno, &events))
+                    .collect();
+                seqs.sort_unstable();
+                seqs
+            },
+            (None, None, None) => events.collect(),
+        };
+        seqs.into_iter()
+            .map(|seq| (seq, &self.entries[seq as usize]))
+            .filter(|(seq, e)| select.matches_fields(*seq, e.sscratch, e.satp, e.sysno as u64))
+            .map(|(seq, e)| (seq, e.offset))
+            .collect()
+    }
+}
+
+/// Numbers of events in window with key, by binary search in keys
+/// sorted by key and event number.
+fn keys_in(keys: &[(u64, u64)], key: u64, events: &Range<u64>) -> Vec<u64> {
+    let start = keys.partition_point(|k| *k < (key, events.start));
+    let end = keys.partition_point(|k| *k < (key, events.end));
+    keys[start..end].iter().map(|(_, seq)| *seq).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Index of events in tasks 1..=3, address spaces 10..=11 and
+    /// syscalls 63, 64 and 172.
+    fn index() -> TraceIndex {
+        let entries = (0..20)
+            .map(|seq| IndexEntry {
+                offset: seq * 200,
+                sscratch: seq % 3 + 1,
+                satp: seq % 2 + 10,
+                sysno: [63, 64, 172][(seq / 2 % 3) as usize],
+                inout: (seq % 2) as u32,
+            })
+            .collect();
+        TraceIndex::new(20 * 200, entries)
+    }
+
+    #[test]
+    fn lookup_matches_scan() {
+        let index = index();
+        let windows = [None, Some(0..20), Some(3..11), Some(15..40), Some(8..8), Some(30..40)];
+        let sysnos = [None, Some(vec![63]), Some(vec![64, 172]), Some(vec![1])];
+        for task in [None, Some(1), Some(3), Some(4)] {
+            for satp in [None, Some(10), Some(11)] {
+                for events in windows.clone() {
+                    for sysnos in sysnos.clone() {
+                        let sysnos = sysnos.map(HashSet::from_iter);
+                        let select = Selection { task, satp, events: events.clone(), sysnos };
+                        let scan: Vec<_> = index.entries.iter()
+                            .zip(0..)
+                            .filter(|(e, seq)| {
+                                select.matches_fields(*seq, e.sscratch, e.satp, e.sysno as u64)
+                            })
+                            .map(|(e, seq)| (seq, e.offset))
+                            .collect();
+                        assert_eq!(index.lookup(&select), scan, "{:?}", select);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seek_by_sysno() {
+        use crate::event::tests::{task_record, TempTrace};
+        use crate::event::TE_SIZE;
+        use crate::sysno::{SYS_GETPID, SYS_READ};
+        use crate::{IN, OUT};
+
+        let read = |inout| task_record(1, inout, [0, 0x2000, 3, 0, 0, 0, 0, SYS_READ], &[(1, b"abc")]);
+        let getpid = |inout| task_record(1, inout, [0, 0, 0, 0, 0, 0, 0, SYS_GETPID], &[]);
+        let mut data = [getpid(IN), getpid(OUT), read(IN), read(OUT), getpid(IN)].concat();
+        let trace = TempTrace::new("sysno", &data);
+        let idx_path = TraceIndex::path_for(trace.path());
+        TraceIndex::build(trace.path(), false).unwrap().save(&idx_path).unwrap();
+        // Damage a getpid which isn't selected, which only a scan reads
+        let last = data.len() - TE_SIZE;
+        data[last] ^= 0xff;
+        fs::write(trace.path(), &data).unwrap();
+
+        let select = Selection { sysnos: Some(HashSet::from([SYS_READ])), ..Default::default() };
+        for mmap in [false, true] {
+            let opts = crate::Options { level: 1, mmap, select: select.clone(), ..Default::default() };
+            let events: Vec<_> = opts.open_reader(trace.path()).unwrap()
+                .map(|evt| evt.map(|evt| (evt.seq, evt.head.ax[7], evt.payloads.len())))
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(events, [(2, SYS_READ, 1), (3, SYS_READ, 1)]);
+        }
+        // Without index, the damaged getpid is read and fails
+        fs::remove_file(&idx_path).unwrap();
+        let opts = crate::Options { level: 1, select, ..Default::default() };
+        assert!(opts.open_reader(trace.path()).unwrap().any(|evt| evt.is_err()));
+    }
+
+    #[test]
+    fn reject_offset_beyond_data() {
+        let mut index = index();
+        index.data_size = index.entries.last().unwrap().offset;
+        let path = std::env::temp_dir()
+            .join(format!("lktrace-{}-index.idx", std::process::id()));
+        let path = path.to_str().unwrap();
+        index.save(path).unwrap();
+        let loaded = TraceIndex::load(path);
+        let _ = fs::remove_file(path);
+        assert!(matches!(loaded, Err(TraceError::BadIndex { .. })));
+    }
+}
@@ -1,22 +1,65 @@
-use std::fs::File;
-use std::io::{Result, BufReader};
-use crate::event::{TE_SIZE, parse_event};
-use crate::sysno::MAX_SYSCALL_NBR;
+//! Level 0: raw output of trace events.
 
-pub(crate) fn analyse(path: &str) -> Result<()> {
-    let f = File::open(path)?;
-    let mut filesize = f.metadata()?.len() as usize;
-    let mut reader = BufReader::new(f);
+use crate::Options;
+use crate::error::Result;
+use crate::event::TraceHead;
+use crate::format::{raw_json, report_skipped, Format};
+use crate::parallel::Decoded;
 
-    while filesize >= TE_SIZE {
-        let evt = parse_event(&mut reader, 0)?;
-        let advance = evt.head.totalsize as usize;
-        println!("tid: {:#x} -> ({})[{:#x}, {:#x}, {}]; pid: {:#x}",
-            evt.head.sscratch, evt.head.inout, evt.head.cause,
-            evt.head.epc, evt.head.ax[7], evt.head.satp);
-        assert!(evt.head.ax[7] < MAX_SYSCALL_NBR);
+pub fn analyse(path: &str, opts: &Options) -> Result<()> {
+    // Both need completed syscalls, i.e. grouped events
+    if opts.format == Format::Strace || opts.summary.is_some() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "strace format and summary need level 1 or 2",
+        ).into());
+    }
+    if opts.mapped() {
+        return analyse_mapped(path, opts);
+    }
+    let mut reader = opts.open_reader(path)?;
+    for evt in reader.by_ref() {
+        let evt = evt?;
+        if opts.filter.matches_raw(&evt) {
+            println!("{}", raw_line(&evt.head, evt.offset, opts.format));
+        }
+    }
+    if opts.lenient {
+        report_skipped(opts.format, reader.skipped(), 0);
+    }
+    Ok(())
+}
 
-        filesize -= advance;
+/// Same as [`analyse`], but lines are formatted from views of mapped
+/// data, on worker threads with several jobs. An event is only made for
+/// filter.
+fn analyse_mapped(path: &str, opts: &Options) -> Result<()> {
+    let format = opts.format;
+    let filter = opts.filter.clone();
+    let index = opts.load_index(path)?;
+    let mut lines = Decoded::open(path, opts, index.as_ref(), Box::new(move |_, view| {
+        let line = raw_line(&view.head, view.offset, format);
+        if !filter.is_empty() && !filter.matches_raw(&view.into_event(0)?) {
+            return Ok(None);
+        }
+        Ok(Some(line))
+    }))?;
+    for line in lines.by_ref() {
+        if let Some(line) = line? {
+            println!("{}", line);
+        }
+    }
+    if opts.lenient {
+        report_skipped(opts.format, lines.skipped(), 0);
     }
     Ok(())
 }
+
+fn raw_line(head: &TraceHead, offset: u64, format: Format) -> String {
+    match format {
+        Format::Text => format!("tid: {:#x} -> ({})[{:#x}, {:#x}, {}]; pid: {:#x}",
+            head.sscratch, head.inout, head.cause, head.epc, head.ax[7], head.satp),
+        Format::Jsonl => raw_json(head, offset).to_string(),
+        Format::Strace => unreachable!("strace format of raw events"),
+    }
+}
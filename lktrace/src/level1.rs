@@ -1,49 +1,325 @@
-use std::fs::File;
-use std::io::Result;
-use std::io::BufReader;
-use std::collections::{BTreeMap, HashSet};
-use crate::{IN, OUT};
+//! Level 1: group trace events by thread.
+
+use crate::error::{Result, TraceError};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::mem;
+use crate::{Options, IN, OUT};
 use crate::sysno::*;
-use crate::event::{TraceEvent, TraceFlow, USER_ECALL};
+use crate::event::{Skipped, TraceEvent, TraceFlow};
 use crate::event::{parse_sigaction, SigStage};
-use crate::event::{print_events, LK_MAGIC, TE_SIZE, parse_event};
-
-pub(crate) fn analyse(path: &str) -> Result<()> {
-    let f = File::open(path)?;
-    let mut filesize = f.metadata()?.len() as usize;
-    let mut reader = BufReader::new(f);
-
-    let mut sighand_set: HashSet<usize> = HashSet::new();
-    let mut events_map: BTreeMap<u64, TraceFlow> = BTreeMap::new();
-    let mut vfork_req: Vec<TraceEvent> = vec![];
-    let mut task_seq: Vec<u64> = vec![];
-    while filesize >= TE_SIZE {
-        let mut evt = parse_event(&mut reader, 1)?;
-        let advance = evt.head.totalsize as usize;
-        assert_eq!(evt.head.magic, LK_MAGIC);
-        assert_eq!(evt.head.headsize, TE_SIZE as u16);
-        assert!(evt.head.totalsize >= evt.head.headsize as u32);
+use crate::event::{format_events, format_task_events, SeqNames};
+use crate::fd::FdTables;
+use crate::clone::cloned_by;
+use crate::signature::{signature, Param, Ret};
+use crate::errno::is_error;
+use crate::format::{event_json, report_skipped, strace_lines, tasks_json, Format};
+use crate::parallel::map_ordered;
+use crate::summary::Summary;
+
+/// Group trace events by thread. Level decides how events are formatted,
+/// e.g. level 2 replaces ids with seq-names.
+///
+/// By default, events of a task are printed together when it exits.
+/// In streaming mode, each syscall is printed as soon as it completes,
+/// so memory doesn't grow with the length of trace.
+pub fn analyse(path: &str, opts: &Options) -> Result<()> {
+    let names = SeqNames::new(opts.level);
+    let mut printer = Printer { opts, names: &names, pending: vec![], events: 0 };
+    let mut summary = opts.summary.map(Summary::new);
+    let grouped = group(path, opts, &names, |tid, flow| {
+        if let Some(summary) = &mut summary {
+            summary.add(tid, &flow);
+        }
+        if !opts.summary_only {
+            printer.print(tid, flow);
+        }
+        Ok(())
+    });
+    // Flows completed before an error are printed, as they are when
+    // not held by printer
+    printer.flush();
+    let grouped = grouped?;
+
+    if let Some(summary) = &summary {
+        summary.print(opts.format, &names);
+    }
+    let tasks = grouped.task_seq.iter().map(|tid| names.task(*tid));
+    match opts.format {
+        // Tasks aren't recorded in streaming mode
+        _ if opts.summary_only || opts.stream => (),
+        Format::Text => {
+            println!("Task sequence: ");
+            tasks.for_each(|task| println!("{}", task));
+        },
+        Format::Jsonl => println!("{}", tasks_json(&tasks.collect::<Vec<_>>())),
+        Format::Strace => (),
+    }
+    if opts.lenient {
+        report_skipped(opts.format, grouped.skipped, grouped.dropped);
+    }
+    Ok(())
+}
+
+/// What is left of trace after all its flows are grouped.
+pub struct Grouped {
+    /// Tasks in order of their first event, empty in streaming mode
+    pub task_seq: Vec<u64>,
+    /// Data skipped by a lenient reader
+    pub skipped: Skipped,
+    /// Number of events dropped by a lenient grouper
+    pub dropped: usize,
+}
+
+/// Read events of trace data file selected by opts, and group them by
+/// task. Sink is given each flow as soon as it completes, i.e. when its
+/// task exits, or each completed syscall in streaming mode, where
+/// memory doesn't grow with the number of tasks. Flows of tasks which
+/// haven't exited are given at the end, in order of their first event.
+/// At level 2, ids are given seq-names in names as events are read.
+///
+/// Events which don't pass the filter of opts are removed from flows,
+/// and flows left empty aren't given to sink.
+pub fn group<F>(path: &str, opts: &Options, names: &SeqNames, mut sink: F) -> Result<Grouped>
+where
+    F: FnMut(u64, TraceFlow) -> Result<()>,
+{
+    let mut sink = |tid, mut flow: TraceFlow| {
+        if opts.filter.is_empty() {
+            return sink(tid, flow);
+        }
+        flow.events.retain(|evt| opts.filter.matches(evt));
+        if flow.events.is_empty() {
+            return Ok(());
+        }
+        sink(tid, flow)
+    };
+    let mut reader = opts.open_reader(path)?;
+    let mut grouper = Grouper::new()
+        .partial(!opts.selection().is_all())
+        .record_tasks(!opts.stream)
+        .names(names.clone());
+    let mut dropped = 0;
+    for evt in reader.by_ref() {
+        let evt = evt?;
+        let tid = evt.head.sscratch;
+        match grouper.push(evt) {
+            Ok(Some((tid, flow))) => sink(tid, flow)?,
+            Ok(None) if opts.stream => {
+                let flow = grouper.flush(tid);
+                if !flow.events.is_empty() {
+                    sink(tid, flow)?;
+                }
+            },
+            Ok(None) => (),
+            Err(e) if opts.lenient => {
+                warn!("{}, drop it", e);
+                dropped += 1;
+            },
+            Err(e) => return Err(e),
+        }
+    }
+
+    for (tid, flow) in grouper.remaining() {
+        sink(tid, flow)?;
+    }
+    Ok(Grouped {
+        task_seq: grouper.task_seq,
+        skipped: reader.skipped(),
+        dropped,
+    })
+}
+
+/// Syscalls which grouping reads state from, whatever else is selected:
+/// clone pairs children, exits complete flows, signal handlers are found
+/// by rt_sigaction and return by rt_sigreturn, fd tables are rebuilt by
+/// execve and the fd syscalls, and pids are given by getpid and gettid.
+/// Signals delivered in syscalls which aren't selected are still missed.
+pub const GROUPING_SYSCALLS: [u64; 13] = [
+    SYS_DUP, SYS_DUP3, SYS_FCNTL, SYS_OPENAT, SYS_CLOSE, SYS_EXIT, SYS_EXIT_GROUP,
+    SYS_RT_SIGACTION, SYS_RT_SIGRETURN, SYS_GETPID, SYS_GETTID, SYS_CLONE, SYS_EXECVE,
+];
 
+/// Name pid arguments of request, or the pid which a completed syscall
+/// returns.
+fn name_pids(names: &SeqNames, evt: &TraceEvent) {
+    if !names.is_enabled() {
+        return;
+    }
+    let sig = signature(evt.head.ax[7]);
+    if evt.head.inout == IN {
+        for (index, arg) in sig.args.iter().enumerate() {
+            if arg.param == Param::Pid {
+                names.pid(evt.head.ax[index] as i64);
+            }
+        }
+    } else if sig.ret == Ret::Pid && !is_error(evt.result) {
+        names.pid(evt.result);
+    }
+}
+
+/// Number of events held by [`Printer`] before they are formatted.
+const PENDING_EVENTS: usize = 64 * 1024;
+
+/// Prints flows of tasks in order. With several jobs, flows are held
+/// and then formatted on worker threads.
+struct Printer<'a> {
+    opts: &'a Options,
+    names: &'a SeqNames,
+    pending: Vec<(u64, TraceFlow)>,
+    /// Number of events in pending flows
+    events: usize,
+}
+
+impl Printer<'_> {
+    fn print(&mut self, tid: u64, flow: TraceFlow) {
+        if self.opts.jobs <= 1 {
+            print!("{}", self.format(tid, &flow));
+            return;
+        }
+        self.events += flow.events.len();
+        self.pending.push((tid, flow));
+        if self.events >= PENDING_EVENTS {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let out = map_ordered(&self.pending, self.opts.jobs, |(tid, flow)| {
+            self.format(*tid, flow)
+        });
+        out.iter().for_each(|s| print!("{}", s));
+        self.pending.clear();
+        self.events = 0;
+    }
+
+    fn format(&self, tid: u64, flow: &TraceFlow) -> String {
+        let task = self.names.task(tid);
+        let mut out = String::new();
+        match self.opts.format {
+            Format::Text if self.opts.stream => out = format_task_events(&task, flow),
+            Format::Text => out = format_events(&task, &flow.events),
+            Format::Jsonl => {
+                for (idx, evt) in flow.events.iter().enumerate() {
+                    let _ = writeln!(out, "{}", event_json(&task, flow.flushed + idx, evt));
+                }
+            },
+            Format::Strace => {
+                // Numeric pid as strace prints, if it's known
+                let pid = match flow.pid {
+                    Some(pid) => self.names.pid(pid).unwrap_or_else(|| pid.to_string()),
+                    None => task,
+                };
+                // strace shows the clone only in its parent, not the
+                // copy of the request the child starts with
+                let skip = usize::from(cloned_by(tid, flow).is_some());
+                flow.events[skip..].iter().for_each(|evt| strace_lines(&mut out, &pid, evt));
+            },
+        }
+        out
+    }
+}
+
+/// Groups trace events by task (sscratch).
+///
+/// Each request is merged with its reply into a single event, clone
+/// replays in the child are paired with the parent's request, and
+/// signal delivery is recorded as `SigStage` events. Fd arguments are
+/// annotated with their paths from the fd tables of tasks.
+#[derive(Default)]
+pub struct Grouper {
+    sighand_set: HashSet<u64>,
+    fds: FdTables,
+    events_map: BTreeMap<u64, TraceFlow>,
+    /// Clone requests whose children haven't started, with fd tables
+    /// held for them
+    vfork_req: Vec<(TraceEvent, usize)>,
+    /// Results of clone whose children haven't started, by number of
+    /// request
+    clone_pids: HashMap<u64, i64>,
+    /// Children of clone whose parents haven't got the result, by
+    /// number of request
+    clone_children: HashMap<u64, u64>,
+    /// Tasks which haven't exited, in order of their first event
+    live: Vec<u64>,
+    /// All tasks in order of their first event, if recorded
+    task_seq: Vec<u64>,
+    record_tasks: bool,
+    partial: bool,
+    names: SeqNames,
+}
+
+impl Grouper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Events are only part of trace, e.g. selected by task. Then a
+    /// task may start with a reply whose request isn't in the events,
+    /// and such replies are dropped, as are returns of signal handlers
+    /// whose delivery isn't in the events.
+    pub fn partial(mut self, partial: bool) -> Self {
+        self.partial = partial;
+        self
+    }
+
+    /// Record all tasks in order of their first event, see
+    /// [`Self::task_seq`]. It grows with the number of tasks.
+    pub fn record_tasks(mut self, record: bool) -> Self {
+        self.record_tasks = record;
+        self
+    }
+
+    /// Give seq-names to ids in names, in order of first appearance.
+    pub fn names(mut self, names: SeqNames) -> Self {
+        self.names = names;
+        self
+    }
+
+    /// Feed the next event of trace.
+    /// Returns the flow of a task as soon as it calls exit or exit_group.
+    pub fn push(&mut self, mut evt: TraceEvent) -> Result<Option<(u64, TraceFlow)>> {
         debug!("tid: {:#x} -> ({})[{:#x}, {:#x}, {}]; pid: {:#x}",
             evt.head.sscratch, evt.head.inout, evt.head.cause,
             evt.head.epc, evt.head.ax[7], evt.head.satp);
 
-        assert_eq!(evt.head.cause, USER_ECALL);
-
         let tid = evt.head.sscratch;
-        let flow = match events_map.get_mut(&tid) {
+        let sysno = evt.head.ax[7];
+        let offset = evt.offset;
+        evt.names = self.names.clone();
+        let unmatched = |reason| TraceError::Unmatched {
+            offset, tid, sysno, reason,
+        };
+        let flow = match self.events_map.get_mut(&tid) {
             Some(q) => q,
             None => {
                 // Start of each event is either req or clone.replay
-                assert!(evt.head.inout == IN || evt.head.ax[7] == SYS_CLONE);
-                task_seq.push(tid);
-                debug!("New events: {:#x}", tid);
-                events_map.insert(tid, TraceFlow::new());
-                let flow = events_map.get_mut(&tid).unwrap();
-                if evt.head.inout == OUT {
-                    let req = vfork_req.pop().unwrap();
-                    flow.events.push(req);
+                let first = if evt.head.inout == IN {
+                    None
+                } else if sysno == SYS_CLONE && !self.vfork_req.is_empty() {
+                    let (req, table) = self.vfork_req.pop().unwrap();
+                    self.fds.attach(tid, table);
+                    if !self.clone_pids.contains_key(&req.seq) {
+                        self.clone_children.insert(req.seq, tid);
+                    }
+                    Some(req)
+                } else if self.partial {
+                    debug!("drop reply without request: {:#x}", tid);
+                    return Ok(None);
+                } else if sysno == SYS_CLONE {
+                    return Err(unmatched("no pending clone for new task"));
+                } else {
+                    return Err(unmatched("new task starts with a reply"));
+                };
+                self.live.push(tid);
+                if self.record_tasks {
+                    self.task_seq.push(tid);
                 }
+                self.names.add(tid as i64);
+                debug!("New events: {:#x}", tid);
+                let flow = self.events_map.entry(tid).or_default();
+                flow.pid = first.as_ref().and_then(|req| self.clone_pids.remove(&req.seq));
+                flow.events.extend(first);
                 flow
             },
         };
@@ -51,26 +327,36 @@ pub(crate) fn analyse(path: &str) -> Result<()> {
         match evt.head.inout {
             IN => {
                 debug!("request: {}", evt.head.ax[7]);
+                self.fds.request(tid, &mut evt);
+                name_pids(&self.names, &evt);
                 if let Some(last) = flow.events.last() {
                     if last.head.inout != OUT {
-                        println!("======================= might be killed: {}", last.head.ax[7]);
+                        warn!("might be killed: {}", last.head.ax[7]);
                     }
                 }
 
-                let sysno = evt.head.ax[7];
                 match sysno {
                     SYS_CLONE => {
-                        vfork_req.push(evt.clone());
+                        let table = self.fds.hold(tid, evt.head.ax[0]);
+                        self.vfork_req.push((evt.clone(), table));
                         flow.events.push(evt);
                     },
+                    SYS_RT_SIGRETURN if flow.signal_stack.is_empty() && self.partial => {
+                        debug!("drop rt_sigreturn without signal: {:#x}", tid);
+                    },
                     SYS_RT_SIGRETURN => {
                         debug!("signal exit: ");
-                        flow.events.push(flow.signal_stack.pop().unwrap());
+                        let mut last = flow.signal_stack.pop()
+                            .ok_or_else(|| unmatched("rt_sigreturn without signal"))?;
+                        // Interrupted syscall ends when its signal handler returns
+                        last.reply_seq = Some(evt.seq);
+                        flow.events.push(last);
                     },
-                    SYS_EXIT_GROUP => {
+                    SYS_EXIT | SYS_EXIT_GROUP => {
                         flow.events.push(evt);
-                        print_events(tid, &flow.events);
-                        events_map.remove(&tid);
+                        self.fds.exit(tid);
+                        self.live.retain(|live| *live != tid);
+                        return Ok(self.events_map.remove(&tid).map(|flow| (tid, flow)));
                     },
                     _ => {
                         flow.events.push(evt);
@@ -78,56 +364,254 @@ pub(crate) fn analyse(path: &str) -> Result<()> {
                 }
             },
             OUT => {
-                {
-                    let last = flow.events.last().expect("No requests in event queue!");
-                    assert_eq!(evt.head.ax[7], last.head.ax[7], "{:#x} != {:#x}", evt.head.ax[7], last.head.ax[7]);
-                    if evt.head.ax[7] != last.head.ax[7] {
-                        debug!("unmatch: {} != {}", evt.head.ax[7], last.head.ax[7]);
-                    }
+                let last = flow.events.last().ok_or_else(|| unmatched("no requests in event queue"))?;
+                if sysno != last.head.ax[7] {
+                    debug!("unmatch: {} != {}", sysno, last.head.ax[7]);
+                    return Err(unmatched("reply to another syscall"));
                 }
 
-                if evt.head.ax[7] == SYS_RT_SIGACTION {
+                if sysno == SYS_RT_SIGACTION {
                     if let Some((sigaction, _)) = parse_sigaction(&evt) {
                         debug!("sigaction.handler {:#x}", sigaction.handler);
-                        sighand_set.insert(sigaction.handler);
+                        self.sighand_set.insert(sigaction.handler);
                     }
                 }
 
                 // Todo: to distinguish signal by epc is NOT a proper method.
                 // Try to find exact method.
-                if sighand_set.contains(&(evt.head.epc as usize)) {
-                    assert!(evt.head.ax[7] != SYS_EXECVE);
+                if sysno != SYS_EXECVE && self.sighand_set.contains(&evt.head.epc) {
                     let mut last = flow.events.pop().unwrap();
                     last.signal = SigStage::Exit(evt.head.ax[0]);
                     flow.signal_stack.push(last);
 
                     debug!("signal enter: {}", evt.head.ax[0]);
-                    let mut sig_req = TraceEvent::default();
-                    sig_req.signal = SigStage::Enter(evt.head.ax[0]);
+                    let mut sig_req = TraceEvent {
+                        seq: evt.seq,
+                        signal: SigStage::Enter(evt.head.ax[0]),
+                        ..Default::default()
+                    };
                     sig_req.head.inout = OUT;
                     sig_req.head.ax[0] = evt.head.ax[0];
                     flow.events.push(sig_req);
                 } else {
-                    let last = flow.events.last_mut().expect("No requests in event queue!");
-                    debug!("event out: {}", evt.head.ax[7]);
+                    let last = flow.events.last_mut().unwrap();
+                    debug!("event out: {}", sysno);
                     last.result = evt.head.ax[0] as i64;
+                    last.reply_seq = Some(evt.seq);
                     last.payloads.append(&mut evt.payloads);
                     last.head.inout = OUT;
+                    self.fds.reply(tid, last);
+                    name_pids(&self.names, last);
                     debug!("replay: {}", last);
+                    let (seq, result) = (last.seq, last.result);
+                    match sysno {
+                        // No child will take the request
+                        SYS_CLONE if is_error(result) => {
+                            if let Some(i) = self.vfork_req.iter().position(|(req, _)| req.seq == seq) {
+                                let (_, table) = self.vfork_req.remove(i);
+                                self.fds.release(table);
+                            }
+                        },
+                        // Clone in the child returns 0
+                        SYS_CLONE if result > 0 => self.clone_result(seq, result),
+                        SYS_GETTID if !is_error(result) => flow.pid = Some(result),
+                        SYS_GETPID if !is_error(result) => {
+                            flow.pid.get_or_insert(result);
+                        },
+                        _ => (),
+                    }
                 }
             },
             _ => unreachable!(),
         }
+        Ok(None)
+    }
+
+    /// Result of clone request seq in the parent, which is the pid of
+    /// child.
+    fn clone_result(&mut self, seq: u64, pid: i64) {
+        match self.clone_children.remove(&seq) {
+            Some(child) => {
+                if let Some(flow) = self.events_map.get_mut(&child) {
+                    flow.pid = Some(pid);
+                }
+            },
+            None => {
+                self.clone_pids.insert(seq, pid);
+            },
+        }
+    }
+
+    /// Take completed events of task, but leave its pending request.
+    /// The returned flow records where these events start in the task.
+    pub fn flush(&mut self, tid: u64) -> TraceFlow {
+        let mut ret = TraceFlow::new();
+        if let Some(flow) = self.events_map.get_mut(&tid) {
+            let mut n = flow.events.len();
+            if flow.events.last().is_some_and(|last| last.head.inout == IN) {
+                n -= 1;
+            }
+            ret.flushed = flow.flushed;
+            ret.pid = flow.pid;
+            ret.events = flow.events.drain(..n).collect();
+            flow.flushed += n;
+        }
+        ret
+    }
 
-        filesize -= advance;
+    /// Take flows of tasks which haven't exited, in order of their
+    /// first event.
+    pub fn remaining(&mut self) -> Vec<(u64, TraceFlow)> {
+        let mut flows = mem::take(&mut self.events_map);
+        mem::take(&mut self.live)
+            .into_iter()
+            .filter_map(|tid| Some((tid, flows.remove(&tid)?)))
+            .collect()
     }
 
-    for (id, flow) in events_map.iter() {
-        print_events(*id, &flow.events);
+    /// All tasks in order of their first event, if they are recorded.
+    pub fn task_seq(&self) -> &[u64] {
+        &self.task_seq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::tests::{task_record, TempTrace};
+    use crate::event::{TracePayload, USER_ECALL};
+    use crate::signal::SIGACTION_SIZE;
+
+    /// Handler of SIGUSR1 in trace of [`workload`]
+    const HANDLER: u64 = 0x9000;
+    const SIGUSR1: u64 = 10;
+
+    /// Raw event of task at level 2. A reply returns ax[0].
+    fn event(tid: u64, inout: u64, ax: [u64; 8]) -> TraceEvent {
+        let mut evt = TraceEvent { level: 2, ..Default::default() };
+        evt.head.inout = inout;
+        evt.head.cause = USER_ECALL;
+        evt.head.epc = 0x1000;
+        evt.head.ax = ax;
+        evt.head.sscratch = tid;
+        evt
     }
-    println!("Task sequence: ");
-    for tid in task_seq {
-        println!("{:#x}", tid);
+
+    fn call(trace: &mut Vec<TraceEvent>, tid: u64, sysno: u64, args: [u64; 3], ret: u64) {
+        trace.push(event(tid, IN, [args[0], args[1], args[2], 0, 0, 0, 0, sysno]));
+        trace.push(event(tid, OUT, [ret, args[1], args[2], 0, 0, 0, 0, sysno]));
+    }
+
+    /// Parent forks a child, which is interrupted by SIGUSR1 in read,
+    /// and waits for it. Tasks and pids are given by the kernel.
+    fn workload(parent: u64, child: u64, ppid: u64, cpid: u64) -> Vec<TraceEvent> {
+        let mut trace = vec![];
+        call(&mut trace, parent, SYS_GETPID, [0; 3], ppid);
+        let mut act = vec![0; SIGACTION_SIZE];
+        act[..8].copy_from_slice(&HANDLER.to_le_bytes());
+        call(&mut trace, parent, SYS_RT_SIGACTION, [SIGUSR1, 0x8000, 0], 0);
+        let sigaction = trace.last_mut().unwrap();
+        sigaction.payloads.push(TracePayload { inout: OUT, index: 1, data: act.into() });
+        // Child runs first, with a copy of the clone request
+        let clone = [0x1200011, 0, 0, 0, 0, 0, 0, SYS_CLONE];
+        trace.push(event(parent, IN, clone));
+        trace.push(event(child, OUT, [0, 0, 0, 0, 0, 0, 0, SYS_CLONE]));
+        trace.push(event(parent, OUT, [cpid, 0, 0, 0, 0, 0, 0, SYS_CLONE]));
+        call(&mut trace, child, SYS_GETTID, [0; 3], cpid);
+        trace.push(event(child, IN, [0, 0x6000, 100, 0, 0, 0, 0, SYS_READ]));
+        let mut interrupted = event(child, OUT, [SIGUSR1, 0x6000, 100, 0, 0, 0, 0, SYS_READ]);
+        interrupted.head.epc = HANDLER;
+        trace.push(interrupted);
+        call(&mut trace, child, SYS_WRITE, [1, 0x6100, 3], 3);
+        trace.push(event(child, IN, [0, 0, 0, 0, 0, 0, 0, SYS_RT_SIGRETURN]));
+        trace.push(event(child, IN, [0, 0, 0, 0, 0, 0, 0, SYS_EXIT]));
+        call(&mut trace, parent, SYS_WAIT4, [-1i64 as u64, 0, 0], cpid);
+        trace.push(event(parent, IN, [0, 0, 0, 0, 0, 0, 0, SYS_EXIT_GROUP]));
+        for (seq, evt) in trace.iter_mut().enumerate() {
+            evt.seq = seq as u64;
+        }
+        trace
+    }
+
+    /// Level 2 output of trace, flow by flow as tasks exit.
+    fn level2(trace: Vec<TraceEvent>) -> String {
+        let names = SeqNames::new(2);
+        let mut grouper = Grouper::new().names(names.clone());
+        let mut out = String::new();
+        for evt in trace {
+            if let Some((tid, flow)) = grouper.push(evt).unwrap() {
+                out += &format_events(&names.task(tid), &flow.events);
+            }
+        }
+        assert!(grouper.remaining().is_empty());
+        out
+    }
+
+    #[test]
+    fn level2_is_deterministic() {
+        let out = level2(workload(0xffffffc0805be038, 0xffffffc080373018, 1234, 1240));
+        let other = level2(workload(0xffffffc08059e018, 0xffffffc0805be038, 77, 78));
+        assert_eq!(out, other);
+
+        // Child is paired with the clone request, and its read ends
+        // when the handler of signal returns
+        let child = out.split("\n\n").next().unwrap();
+        assert!(child.starts_with("Task[tid_2] ========>\n[0]: clone("), "{}", child);
+        assert!(child.contains("[2]: Signal[SIGUSR1] enter.."), "{}", child);
+        assert!(child.contains("Signal[SIGUSR1] exit..\nread("), "{}", child);
+        assert!(child.contains("[1]: gettid() -> tid_3"), "{}", child);
+        assert!(out.contains("clone(CLONE_CHILD_CLEARTID|CLONE_CHILD_SETTID|SIGCHLD, \
+            stack: NULL, parent_tid: NULL, tls: NULL, child_tid: NULL) -> tid_3"), "{}", out);
+        assert!(out.contains("wait4(-1, NULL, 0x0, NULL) -> tid_3"), "{}", out);
+        assert!(out.contains("getpid() -> tid_1"), "{}", out);
+    }
+
+    /// Events of tasks grouped by opts, each with its index in task,
+    /// and tasks in order of the flows given to sink.
+    fn group_events(trace: &TempTrace, opts: &Options) -> (BTreeMap<u64, Vec<String>>, Vec<u64>) {
+        let mut tasks = BTreeMap::<u64, Vec<String>>::new();
+        let mut order = vec![];
+        group(trace.path(), opts, &SeqNames::new(opts.level), |tid, flow| {
+            let events = tasks.entry(tid).or_default();
+            // Flows of task follow each other
+            assert_eq!(flow.flushed, events.len());
+            for (idx, evt) in flow.events.iter().enumerate() {
+                events.push(format!("[{}]: {}", flow.flushed + idx, evt));
+            }
+            order.push(tid);
+            Ok(())
+        }).unwrap();
+        (tasks, order)
+    }
+
+    #[test]
+    fn stream_matches_grouped() {
+        let (a, b, c) = (0x3000, 0x1000, 0x2000);
+        let sys = |sysno, a0| [a0, 0, 0, 0, 0, 0, 0, sysno];
+        let data = [
+            task_record(a, IN, sys(SYS_GETPID, 0), &[]),
+            task_record(c, IN, sys(SYS_READ, 0), &[]),
+            task_record(b, IN, sys(SYS_GETPID, 0), &[]),
+            task_record(a, OUT, sys(SYS_GETPID, 5), &[]),
+            // Read of c has no reply, it might be killed
+            task_record(c, IN, sys(SYS_GETPID, 0), &[]),
+            task_record(b, OUT, sys(SYS_GETPID, 6), &[]),
+            task_record(c, OUT, sys(SYS_GETPID, 7), &[]),
+            task_record(b, IN, sys(SYS_READ, 0), &[]),
+            task_record(a, IN, sys(SYS_EXIT_GROUP, 0), &[]),
+            task_record(c, IN, sys(SYS_WRITE, 1), &[]),
+        ].concat();
+        let trace = TempTrace::new("stream", &data);
+        let opts = Options { level: 1, ..Default::default() };
+        let (grouped, order) = group_events(&trace, &opts);
+        // Tasks which haven't exited follow in order of first event
+        assert_eq!(order, [a, c, b]);
+        assert_eq!(grouped[&c].len(), 3);
+
+        let opts = Options { stream: true, ..opts };
+        let (streamed, order) = group_events(&trace, &opts);
+        assert_eq!(streamed, grouped);
+        // Pending requests are left to the end
+        assert_eq!(order[order.len() - 2..], [c, b]);
     }
-    Ok(())
 }
@@ -1,5 +1,12 @@
-use std::io::Result;
+//! Level 2: group trace events by thread with seq-names.
 
-pub(crate) fn analyse(_path: &str) -> Result<()> {
-    unimplemented!("analyse");
+use crate::Options;
+use crate::error::Result;
+use crate::level1;
+
+/// Group trace events by thread as level 1 does, but replace task ids,
+/// pids and tids with seq-names (tid_N), so that traces of the same
+/// workload can be diffed.
+pub fn analyse(path: &str, opts: &Options) -> Result<()> {
+    level1::analyse(path, &Options { level: 2, ..opts.clone() })
 }
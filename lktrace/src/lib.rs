@@ -0,0 +1,153 @@
+//! Parser and analyser for `lk_trace.data`, the syscall trace dumped
+//! by the modified qemu.
+//!
+//! Read raw events with [`event::EventReader`] and group them by task
+//! with [`level1::Grouper`]:
+//!
+//! ```no_run
+//! use lktrace::event::{EventReader, print_events};
+//! use lktrace::level1::Grouper;
+//!
+//! let mut grouper = Grouper::new();
+//! for evt in EventReader::open("lk_trace.data", 1)? {
+//!     if let Some((tid, flow)) = grouper.push(evt?)? {
+//!         print_events(&format!("{:#x}", tid), &flow.events);
+//!     }
+//! }
+//! # Ok::<(), lktrace::error::TraceError>(())
+//! ```
+
+#![deny(unsafe_code)]
+
+#[macro_use]
+extern crate log;
+
+pub mod error;
+pub mod level0;
+pub mod level1;
+pub mod level2;
+pub mod event;
+pub mod sysno;
+pub mod errno;
+pub mod mmap;
+pub mod signal;
+pub mod payload;
+pub mod mapped;
+pub mod index;
+pub mod parallel;
+pub mod format;
+pub mod chrome;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod summary;
+pub mod filter;
+pub mod tree;
+pub mod clone;
+pub mod signature;
+pub mod fcntl;
+pub mod fd;
+mod bytes;
+
+/// Event of syscall request (enter kernel).
+pub const IN: u64 = 0;
+/// Event of syscall reply (return to user).
+pub const OUT: u64 = 1;
+
+/// Options of analysis.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Parse level, see [`analyse`]
+    pub level: usize,
+    /// Skip damaged events instead of failing
+    pub lenient: bool,
+    /// Print each syscall as soon as it completes (level 1 and 2)
+    pub stream: bool,
+    /// Map data file into memory instead of reading it, and decode
+    /// events from the map
+    pub mmap: bool,
+    /// Only analyse selected events
+    pub select: index::Selection,
+    /// Number of threads to decode events, sequential if at most 1
+    pub jobs: usize,
+    /// Output format
+    pub format: format::Format,
+    /// Print statistics of syscalls at the end (level 1 and 2)
+    pub summary: Option<summary::Scope>,
+    /// Print statistics instead of events
+    pub summary_only: bool,
+    /// Only print syscalls which pass filter
+    pub filter: filter::Filter,
+}
+
+impl Options {
+    /// Open reader of trace data according to options.
+    pub fn open_reader(&self, path: &str) -> error::Result<event::EventReader> {
+        let index = self.load_index(path)?;
+        if self.mapped() {
+            return event::EventReader::open_mapped(path, self, index.as_ref());
+        }
+        let reader = event::EventReader::open(path, self.level)?.lenient(self.lenient);
+        let select = self.selection();
+        if select.is_all() {
+            return Ok(reader);
+        }
+        Ok(reader.select(select, index.as_ref()))
+    }
+
+    /// Events to read: those selected, and of these only syscalls which
+    /// filter keeps, unless the selection has its own. Above level 0,
+    /// syscalls which grouping needs are read too, see
+    /// [`level1::GROUPING_SYSCALLS`].
+    pub fn selection(&self) -> index::Selection {
+        let mut select = self.select.clone();
+        if let (None, Some(trace)) = (&select.sysnos, &self.filter.trace) {
+            let mut sysnos = trace.clone();
+            if self.level > 0 {
+                sysnos.extend(level1::GROUPING_SYSCALLS);
+            }
+            select.sysnos = Some(sysnos);
+        }
+        select
+    }
+
+    /// Whether data file is mapped into memory, which decoding on
+    /// several jobs needs too.
+    pub fn mapped(&self) -> bool {
+        self.mmap || self.jobs > 1
+    }
+
+    /// Index to look up selected events in, if there is one. All events
+    /// are read without it.
+    pub fn load_index(&self, path: &str) -> error::Result<Option<index::TraceIndex>> {
+        if self.selection().is_all() {
+            return Ok(None);
+        }
+        let index = index::TraceIndex::load_for(path)?;
+        info!("select events {}", if index.is_some() { "by index" } else { "by scan" });
+        Ok(index)
+    }
+}
+
+/// Build index of trace data file, and write it next to the file.
+pub fn build_index(path: &str, opts: &Options) -> error::Result<()> {
+    let index = index::TraceIndex::build(path, opts.lenient)?;
+    let idx_path = index::TraceIndex::path_for(path);
+    index.save(&idx_path)?;
+    println!("{}: {} events", idx_path, index.entries.len());
+    Ok(())
+}
+
+/// Analyse trace data file.
+/// Level 0 prints raw events, level 1 groups events by thread, and
+/// level 2 also replaces ids with seq-names.
+pub fn analyse(path: &str, opts: &Options) -> error::Result<()> {
+    match opts.level {
+        0 => level0::analyse(path, opts),
+        1 => level1::analyse(path, opts),
+        2 => level2::analyse(path, opts),
+        level => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("bad level {}", level),
+        ).into()),
+    }
+}
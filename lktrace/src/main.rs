@@ -1,22 +1,16 @@
 #[macro_use]
 extern crate log;
 
-use std::io::Result;
-use clap::Parser;
+use std::ops::Range;
+use clap::{Parser, Subcommand};
 use simplelog::{SimpleLogger, LevelFilter, Config};
-
-mod level0;
-mod level1;
-mod level2;
-mod event;
-mod sysno;
-mod errno;
-mod mmap;
-mod signal;
-mod payload;
-
-const IN: u64 = 0;
-const OUT: u64 = 1;
+use lktrace::{analyse, build_index, chrome, tree, Options};
+#[cfg(feature = "sqlite")]
+use lktrace::sqlite;
+use lktrace::index::Selection;
+use lktrace::format::Format;
+use lktrace::summary::Scope;
+use lktrace::filter::Filter;
 
 const DEFAULT_LEVEL: usize = 1;
 const DEFAULT_DATA_FILE: &str = "./lk_trace.data";
@@ -32,13 +26,132 @@ struct Cli {
     #[arg(short)]
     level: Option<usize>,
 
+    /// Skip damaged or truncated events instead of failing
+    #[arg(long, global = true)]
+    lenient: bool,
+
+    /// Print each syscall as soon as it completes, instead of
+    /// grouping events of a task until it exits
+    #[arg(long)]
+    stream: bool,
+
+    /// Map data file into memory and decode events from it, instead
+    /// of reading it
+    #[arg(long)]
+    mmap: bool,
+
+    /// Decode events on N threads, 0 for one per cpu.
+    /// Output is the same as decoding on one thread
+    #[arg(short, long, value_name = "N", global = true)]
+    jobs: Option<usize>,
+
+    /// Output format: text, jsonl with one object per line,
+    /// or strace with one line per syscall as strace prints
+    #[arg(long, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Print statistics of syscalls instead of events, over all events
+    /// or per task, process (thread group) or pid (satp), e.g. -c=task
+    #[arg(short = 'c', long, value_name = "SCOPE", num_args = 0..=1, require_equals = true,
+        default_missing_value = "all", conflicts_with = "summary")]
+    summary_only: Option<Scope>,
+
+    /// Print statistics of syscalls after events, as --summary-only
+    #[arg(short = 'C', long, value_name = "SCOPE", num_args = 0..=1, require_equals = true,
+        default_missing_value = "all")]
+    summary: Option<Scope>,
+
+    /// Filter expression, only trace=name,... to print these syscalls
+    #[arg(short = 'e', value_name = "EXPR")]
+    expr: Vec<String>,
+
+    /// Only print failed syscalls
+    #[arg(long)]
+    failed_only: bool,
+
+    /// Only print syscalls failed with errno, by name or number, e.g.
+    /// ENOENT or 2
+    #[arg(long, value_name = "ERRNO")]
+    errno: Option<String>,
+
+    /// Only print syscalls with a path argument, or an fd of a known
+    /// path, containing S
+    #[arg(long, value_name = "S")]
+    path_contains: Option<String>,
+
+    /// Only events of task (sscratch, hex)
+    #[arg(long, value_parser = parse_hex)]
+    task: Option<u64>,
+
+    /// Only events of address space (satp, hex)
+    #[arg(long, value_parser = parse_hex)]
+    pid: Option<u64>,
+
+    /// Only events numbered in A..B, as printed by level 0
+    #[arg(long, value_parser = parse_range)]
+    events: Option<Range<u64>>,
+
     /// Binary trace data file path
     file: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Write index of data file to <file>.idx, used by later
+    /// runs to seek to selected events
+    Index {
+        /// Binary trace data file path
+        file: Option<String>,
+    },
+    /// Export syscalls of tasks to Chrome trace-event JSON, for
+    /// viewing on a timeline in chrome://tracing or Perfetto
+    Chrome {
+        /// Output JSON file path
+        output: String,
+        /// Binary trace data file path
+        file: Option<String>,
+    },
+    /// Print tree of processes and threads, rebuilt from clone,
+    /// execve, wait4 and exit_group
+    Tree {
+        /// Print Graphviz DOT instead of text
+        #[arg(long)]
+        dot: bool,
+        /// Group threads under the leader of their thread group, and
+        /// show address spaces (satp) of tasks
+        #[arg(long)]
+        threads: bool,
+        /// Binary trace data file path
+        file: Option<String>,
+    },
+    /// Load tasks, syscalls with decoded arguments, payloads and clone
+    /// edges into a SQLite database, for queries in SQL
+    #[cfg(feature = "sqlite")]
+    Sqlite {
+        /// Output database path
+        output: String,
+        /// Binary trace data file path
+        file: Option<String>,
+    },
+}
+
+fn parse_hex(s: &str) -> Result<u64, String> {
+    let s = s.trim_start_matches("0x");
+    u64::from_str_radix(s, 16).map_err(|e| e.to_string())
+}
+
+fn parse_range(s: &str) -> Result<Range<u64>, String> {
+    let (start, end) = s.split_once("..").ok_or("expect A..B")?;
+    let start = if start.is_empty() { 0 } else { start.parse().map_err(|e| format!("{}", e))? };
+    let end = if end.is_empty() { u64::MAX } else { end.parse().map_err(|e| format!("{}", e))? };
+    Ok(start..end)
 }
 
 fn main() {
     let log_level = std::env::var("LOG").unwrap_or(String::from("err"));
-    println!("level: {}", log_level);
 
     let log_filter = match log_level.as_str() {
         "err" => LevelFilter::Error,
@@ -51,21 +164,61 @@ fn main() {
     let _ = SimpleLogger::init(log_filter, Config::default());
 
     let cli = Cli::parse();
+    // Only analysis prints text which this line can go with, e.g. not
+    // tree --dot
+    if cli.command.is_none() && cli.format == Format::Text {
+        println!("level: {}", log_level);
+    }
 
     let level = cli.level.unwrap_or(DEFAULT_LEVEL);
-    let path = cli.file.unwrap_or(DEFAULT_DATA_FILE.to_owned());
+    let (action, file) = match &cli.command {
+        Some(Command::Index { file }) => ("index", file.clone()),
+        Some(Command::Chrome { file, .. }) => ("export", file.clone()),
+        #[cfg(feature = "sqlite")]
+        Some(Command::Sqlite { file, .. }) => ("load", file.clone()),
+        Some(Command::Tree { file, .. }) => ("analyse", file.clone()),
+        None => ("analyse", None),
+    };
+    let path = file.or(cli.file).unwrap_or(DEFAULT_DATA_FILE.to_owned());
     info!("Level: {}, Data: {}", level, path);
 
-    if let Err(e) = analyse(&path, level) {
-        error!("analyse {} failed {}", path, e);
+    let mut filter = Filter {
+        failed_only: cli.failed_only,
+        path_contains: cli.path_contains,
+        ..Default::default()
+    };
+    let parsed = cli.errno.iter().try_for_each(|errno| filter.parse_errno(errno))
+        .and_then(|()| cli.expr.iter().try_for_each(|expr| filter.parse_expr(expr)));
+    if let Err(e) = parsed {
+        error!("{}", e);
+        std::process::exit(2);
     }
-}
 
-fn analyse(path: &str, level: usize) -> Result<()> {
-    match level {
-        0 => level0::analyse(path),
-        1 => level1::analyse(path),
-        2 => level2::analyse(path),
-        _ => panic!("bad level {}", level),
+    let opts = Options {
+        level,
+        lenient: cli.lenient,
+        stream: cli.stream,
+        mmap: cli.mmap,
+        select: Selection { task: cli.task, satp: cli.pid, events: cli.events, ..Default::default() },
+        jobs: match cli.jobs {
+            Some(0) => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            jobs => jobs.unwrap_or(1),
+        },
+        format: cli.format,
+        summary: cli.summary.or(cli.summary_only),
+        summary_only: cli.summary_only.is_some(),
+        filter,
+    };
+    let result = match &cli.command {
+        Some(Command::Index { .. }) => build_index(&path, &opts),
+        Some(Command::Chrome { output, .. }) => chrome::export(&path, output, &opts),
+        #[cfg(feature = "sqlite")]
+        Some(Command::Sqlite { output, .. }) => sqlite::export(&path, output, &opts),
+        Some(Command::Tree { dot, threads, .. }) => tree::print(&path, &opts, *dot, *threads),
+        None => analyse(&path, &opts),
+    };
+    if let Err(e) = result {
+        error!("{} {} failed: {}", action, path, e);
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,181 @@
+//! Trace data mapped into memory, with borrowed views of events.
+//!
+//! Views refer to the mapped data directly, without reading through a
+//! buffer. Raw events are formatted from views, and a view is only
+//! turned into an owned event by [`EventView::into_event`] when
+//! analysis needs one, e.g. to group it. Even then its payloads are
+//! left in the map, see [`MappedBytes`].
+
+use std::fs::File;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+use memmap2::Mmap;
+use crate::error::{Result, TraceError};
+use crate::event::{check_head, SeqNames, SigStage, TraceEvent, TraceHead, TracePayload};
+use crate::event::{HEAD_SIG, TE_SIZE};
+use crate::payload::{payload_views, PayloadData, PayloadView};
+
+/// Trace data file mapped into memory. Clones share the map.
+#[derive(Clone)]
+pub struct MappedTrace {
+    map: Arc<Mmap>,
+}
+
+impl MappedTrace {
+    pub fn open(path: &str) -> Result<Self> {
+        let f = File::open(path)?;
+        // SAFETY: trace data is only read. Qemu must not write the file
+        // while it is analysed, as for any other reader.
+        #[allow(unsafe_code)]
+        let map = unsafe { Mmap::map(&f)? };
+        Ok(Self { map: Arc::new(map) })
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Len bytes of data at offset, which must be in data.
+    fn bytes(&self, offset: u64, len: usize) -> MappedBytes {
+        let start = offset as usize;
+        MappedBytes { map: self.map.clone(), range: start..start + len }
+    }
+
+    /// Iterate over views of events from the start of data.
+    /// Iteration stops after the first error.
+    pub fn views(&self) -> EventViews<'_> {
+        EventViews { trace: self, offset: 0 }
+    }
+
+    /// View of the event at offset. Its head is checked, but payloads
+    /// are only checked when they are split by [`EventView::payloads`].
+    pub fn view_at(&self, offset: u64) -> Result<EventView<'_>> {
+        let data = self.data();
+        let left = data.len().saturating_sub(offset as usize);
+        if left < TE_SIZE {
+            return Err(TraceError::Truncated { offset, need: TE_SIZE, left });
+        }
+        let bytes = &data[offset as usize..];
+        let head = TraceHead::decode(bytes).unwrap();
+        check_head(&head, offset)?;
+        let totalsize = head.totalsize as usize;
+        if totalsize > left {
+            return Err(TraceError::Truncated { offset, need: totalsize, left });
+        }
+        Ok(EventView {
+            offset,
+            head,
+            bytes: &bytes[..totalsize],
+            trace: self,
+        })
+    }
+
+    /// Offset of the first valid event head at or after from.
+    pub fn find_head(&self, from: u64) -> Option<u64> {
+        self.find_head_in(from..self.data().len() as u64)
+    }
+
+    /// Offset of the first valid event head which starts in range.
+    pub fn find_head_in(&self, range: Range<u64>) -> Option<u64> {
+        let data = self.data();
+        let end = (range.end as usize).min(data.len());
+        let mut pos = range.start as usize;
+        while pos < end {
+            let scan = &data[pos..(end + HEAD_SIG.len() - 1).min(data.len())];
+            let i = scan.windows(HEAD_SIG.len()).position(|w| w == HEAD_SIG)?;
+            let candidate = (pos + i) as u64;
+            if self.view_at(candidate).is_ok() {
+                return Some(candidate);
+            }
+            pos += i + 1;
+        }
+        None
+    }
+}
+
+/// Borrowed view of an event in mapped trace data.
+#[derive(Clone)]
+pub struct EventView<'a> {
+    /// Offset of event in data file
+    pub offset: u64,
+    /// Head decoded from the first TE_SIZE bytes
+    pub head: TraceHead,
+    bytes: &'a [u8],
+    trace: &'a MappedTrace,
+}
+
+impl<'a> EventView<'a> {
+    /// Raw bytes of the whole event, head and payloads.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Split payloads of event.
+    pub fn payloads(&self) -> Result<Vec<PayloadView<'a>>> {
+        payload_views(&self.bytes[TE_SIZE..], self.offset + TE_SIZE as u64)
+    }
+
+    /// Owned event, as parsed by [`crate::event::parse_event`]. Its
+    /// payloads still refer to the map instead of copies of their data.
+    pub fn into_event(self, level: usize) -> Result<TraceEvent> {
+        let payloads = self.payloads()?
+            .into_iter()
+            .map(|p| TracePayload {
+                inout: self.head.inout,
+                index: p.index,
+                data: PayloadData::Mapped(self.trace.bytes(p.offset, p.data.len())),
+            })
+            .collect();
+        Ok(TraceEvent {
+            head: self.head,
+            offset: self.offset,
+            seq: 0,
+            reply_seq: None,
+            result: 0,
+            payloads,
+            signal: SigStage::Empty,
+            fd_paths: vec![],
+            names: SeqNames::default(),
+            raw_fmt: false,
+            level,
+        })
+    }
+}
+
+/// Bytes of mapped trace data, which keep the map alive.
+#[derive(Clone)]
+pub struct MappedBytes {
+    map: Arc<Mmap>,
+    range: Range<usize>,
+}
+
+impl Deref for MappedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map[self.range.clone()]
+    }
+}
+
+/// Iterator over views of events, see [`MappedTrace::views`].
+pub struct EventViews<'a> {
+    trace: &'a MappedTrace,
+    offset: u64,
+}
+
+impl<'a> Iterator for EventViews<'a> {
+    type Item = Result<EventView<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = self.trace.data().len() as u64;
+        if self.offset >= len {
+            return None;
+        }
+        let view = self.trace.view_at(self.offset);
+        match &view {
+            Ok(view) => self.offset += view.bytes.len() as u64,
+            Err(_) => self.offset = len,
+        }
+        Some(view)
+    }
+}
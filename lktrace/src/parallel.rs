@@ -0,0 +1,318 @@
+//! Parallel decoding of mapped trace data.
+//!
+//! Data is read in batches of a few megabytes per worker thread, so
+//! memory doesn't grow with the length of trace. Each batch is split
+//! into chunks, one per worker. A worker finds the first valid event
+//! head in its chunk, and chains `totalsize` from there to the end of
+//! chunk, checking each event and skipping damaged data as a lenient
+//! reader does. Chains are joined in file order: a chain which doesn't
+//! start where the one before it ended, e.g. at a head-like pattern in
+//! a payload, is found again from there on the calling thread. Selected
+//! events are then decoded on the workers, so events come out exactly
+//! as from a sequential reader. With an index, selected events are
+//! looked up in it instead. With one job, all of it runs on the calling
+//! thread.
+
+use std::collections::VecDeque;
+use std::thread;
+use crate::Options;
+use crate::error::{Result, TraceError};
+use crate::event::Skipped;
+use crate::index::{Selection, TraceIndex};
+use crate::mapped::{EventView, MappedTrace};
+
+/// Number of bytes of data scanned per worker in a batch.
+#[cfg(not(test))]
+const BATCH_BYTES_PER_JOB: u64 = 1 << 20;
+/// Small batches in tests, so that events cross edges of chunks
+#[cfg(test)]
+const BATCH_BYTES_PER_JOB: u64 = 300;
+/// Number of events looked up in index decoded per worker in a batch.
+const BATCH_PER_JOB: usize = 4096;
+
+/// Apply f to all items on up to jobs threads. Results are in the
+/// order of items.
+pub fn map_ordered<T, U, F>(items: &[T], jobs: usize, f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync,
+{
+    let chunk = items.len().div_ceil(jobs.max(1)).max(1);
+    if items.len() <= chunk {
+        return items.iter().map(f).collect();
+    }
+    let f = &f;
+    thread::scope(|s| {
+        let workers: Vec<_> = items
+            .chunks(chunk)
+            .map(|part| s.spawn(move || part.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("decode worker panicked"))
+            .collect()
+    })
+}
+
+/// Event or damaged region found in a chunk of data.
+enum Found {
+    /// Offset of event, and its fields which selection looks at
+    Event { offset: u64, sscratch: u64, satp: u64, sysno: u64 },
+    /// Bytes of damaged data skipped by a lenient reader
+    Skip(usize),
+}
+
+/// Events found by chaining `totalsize` from start to a limit.
+struct Chain {
+    /// Offset of the first event, None if no valid head was found
+    start: Option<u64>,
+    found: Vec<Found>,
+    /// Offset after the last event or damaged region
+    end: u64,
+    /// Error which stops a strict reader, at end
+    error: Option<TraceError>,
+}
+
+/// Chain events from start, until one starts at or after until. Heads
+/// and payloads are checked, and damaged data is skipped up to the next
+/// valid head if lenient, as a sequential reader does.
+fn chain(trace: &MappedTrace, start: u64, until: u64, lenient: bool) -> Chain {
+    let mut chain = Chain { start: Some(start), found: vec![], end: start, error: None };
+    while chain.end < until {
+        let offset = chain.end;
+        let view = trace.view_at(offset).and_then(|view| {
+            view.payloads()?;
+            Ok(view)
+        });
+        match view {
+            Ok(view) => {
+                chain.end += view.bytes().len() as u64;
+                let head = &view.head;
+                chain.found.push(Found::Event {
+                    offset, sscratch: head.sscratch, satp: head.satp, sysno: head.ax[7],
+                });
+            },
+            Err(e) if lenient => {
+                warn!("{}, skip it", e);
+                chain.end = trace.find_head(offset + 1).unwrap_or(trace.data().len() as u64);
+                chain.found.push(Found::Skip((chain.end - offset) as usize));
+            },
+            Err(e) => {
+                chain.error = Some(e);
+                break;
+            },
+        }
+    }
+    chain
+}
+
+/// Decode function of events, run on worker threads. It's given the
+/// number of event in file order and its view.
+pub type DecodeFn<T> = Box<dyn Fn(u64, EventView) -> Result<T> + Send + Sync>;
+
+/// Where the events after batch are.
+enum Next {
+    /// Offset of the next event found by chaining, and its number
+    Scan { offset: u64, seq: u64 },
+    /// Numbers and offsets of selected events, looked up in index
+    Indexed(VecDeque<(u64, u64)>),
+    Done,
+}
+
+/// Selected event or damaged data of a batch, in file order.
+enum Item<T> {
+    Event(Result<T>),
+    /// Bytes of damaged data, 0 for an event looked up in index
+    Skip(usize),
+}
+
+/// Selected event of a batch to decode, or damaged data.
+enum Pending {
+    Event { seq: u64, offset: u64 },
+    Skip(usize),
+}
+
+/// Events of trace data decoded in parallel, in file order.
+pub struct Decoded<T> {
+    trace: MappedTrace,
+    select: Selection,
+    lenient: bool,
+    next: Next,
+    batch: VecDeque<Item<T>>,
+    skipped: Skipped,
+    jobs: usize,
+    decode: DecodeFn<T>,
+}
+
+impl<T: Send> Decoded<T> {
+    /// Map data file to read events selected by opts, or look them up
+    /// in index of data file if it's given. Each event is decoded by
+    /// decode on one of `opts.jobs` threads.
+    pub fn open(
+        path: &str, opts: &Options, index: Option<&TraceIndex>, decode: DecodeFn<T>
+    ) -> Result<Self> {
+        let trace = MappedTrace::open(path)?;
+        let select = opts.selection();
+        let next = match index {
+            Some(index) => Next::Indexed(index.lookup(&select).into()),
+            None => Next::Scan { offset: 0, seq: 0 },
+        };
+        Ok(Self {
+            trace,
+            select,
+            lenient: opts.lenient,
+            next,
+            batch: VecDeque::new(),
+            skipped: Skipped::default(),
+            jobs: opts.jobs.max(1),
+            decode,
+        })
+    }
+
+    /// Data skipped so far.
+    pub fn skipped(&self) -> Skipped {
+        self.skipped
+    }
+
+    /// Find and decode the next batch of events. False at the end of
+    /// data, or after an error.
+    fn fill_batch(&mut self) -> bool {
+        // Events looked up in index are skipped one by one
+        let indexed = matches!(self.next, Next::Indexed(_));
+        let (pending, error) = match &mut self.next {
+            Next::Scan { offset, seq } => {
+                let (offset, seq) = (*offset, *seq);
+                self.scan(offset, seq)
+            },
+            Next::Indexed(offsets) => {
+                let n = offsets.len().min(self.jobs * BATCH_PER_JOB);
+                let pending = offsets.drain(..n)
+                    .map(|(seq, offset)| Pending::Event { seq, offset })
+                    .collect::<Vec<_>>();
+                if offsets.is_empty() {
+                    self.next = Next::Done;
+                }
+                (pending, None)
+            },
+            Next::Done => return false,
+        };
+        let trace = &self.trace;
+        let decode = &self.decode;
+        let lenient = self.lenient && indexed;
+        let items = map_ordered(&pending, self.jobs, |pending| match *pending {
+            Pending::Event { seq, offset } => {
+                let evt = trace.view_at(offset).and_then(|view| {
+                    view.payloads()?;
+                    decode(seq, view)
+                });
+                match evt {
+                    // Other events are where index has them
+                    Err(e) if lenient => {
+                        warn!("{}, skip it", e);
+                        Item::Skip(0)
+                    },
+                    evt => Item::Event(evt),
+                }
+            },
+            Pending::Skip(bytes) => Item::Skip(bytes),
+        });
+        self.batch.extend(items);
+        self.batch.extend(error.map(|e| Item::Event(Err(e))));
+        true
+    }
+
+    /// Find events of the batch which starts at offset with event
+    /// number seq, and choose selected ones to decode. An error which
+    /// stops a strict reader comes after them.
+    fn scan(&mut self, start: u64, mut seq: u64) -> (Vec<Pending>, Option<TraceError>) {
+        let data_end = self.trace.data().len() as u64;
+        let end = data_end.min(start + self.jobs as u64 * BATCH_BYTES_PER_JOB);
+        let size = (end - start).div_ceil(self.jobs as u64).max(1);
+        let edges: Vec<_> = (start..end).step_by(size as usize)
+            .map(|from| from..(from + size).min(end))
+            .collect();
+        let (trace, lenient) = (&self.trace, self.lenient);
+        let chains = map_ordered(&edges, self.jobs, |chunk| {
+            if chunk.start == start {
+                return chain(trace, start, chunk.end, lenient);
+            }
+            match trace.find_head_in(chunk.clone()) {
+                Some(head) => chain(trace, head, chunk.end, lenient),
+                None => Chain { start: None, found: vec![], end: chunk.end, error: None },
+            }
+        });
+        debug!("batch of {} bytes in {} chunks at {:#x}", end - start, edges.len(), start);
+
+        let mut pending = vec![];
+        let mut offset = start;
+        for (chunk, mut found) in edges.into_iter().zip(chains) {
+            if offset >= chunk.end {
+                // Events of chunk were found by the chain before it
+                continue;
+            }
+            if found.start != Some(offset) {
+                debug!("chain at {:?} doesn't join at {:#x}, find it again", found.start, offset);
+                found = chain(&self.trace, offset, chunk.end, self.lenient);
+            }
+            for found in found.found {
+                if self.select.after(seq) {
+                    self.next = Next::Done;
+                    return (pending, None);
+                }
+                match found {
+                    Found::Event { offset, sscratch, satp, sysno } => {
+                        if self.select.matches_fields(seq, sscratch, satp, sysno) {
+                            pending.push(Pending::Event { seq, offset });
+                        }
+                        seq += 1;
+                    },
+                    Found::Skip(bytes) => pending.push(Pending::Skip(bytes)),
+                }
+            }
+            if let Some(e) = found.error {
+                self.next = Next::Done;
+                return (pending, (!self.select.after(seq)).then_some(e));
+            }
+            offset = found.end;
+        }
+        self.next = if offset < data_end && !self.select.after(seq) {
+            Next::Scan { offset, seq }
+        } else {
+            Next::Done
+        };
+        (pending, None)
+    }
+
+    /// Stop iteration after an error.
+    fn stop(&mut self) {
+        self.next = Next::Done;
+        self.batch.clear();
+    }
+}
+
+impl<T: Send> Iterator for Decoded<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(item) = self.batch.pop_front() else {
+                if !self.fill_batch() {
+                    return None;
+                }
+                continue;
+            };
+            match item {
+                Item::Skip(bytes) => {
+                    self.skipped.bytes += bytes;
+                    self.skipped.events += 1;
+                },
+                Item::Event(Ok(evt)) => return Some(Ok(evt)),
+                Item::Event(Err(e)) => {
+                    self.stop();
+                    return Some(Err(e));
+                },
+            }
+        }
+    }
+}
@@ -1,47 +1,159 @@
+use std::fmt::{self, Debug, Formatter};
 use std::io::prelude::*;
-use std::io::Result;
-use std::fs::File;
-use std::io::BufReader;
-use std::mem;
+use std::ops::Deref;
+use crate::bytes::LeReader;
+use crate::error::{Result, TraceError};
 use crate::event::TracePayload;
+use crate::mapped::MappedBytes;
 
-#[repr(C)]
 struct PayloadHead {
     magic: u16,
     index: u16,
     size: u32,
 }
 
-const PH_SIZE: usize = mem::size_of::<PayloadHead>();
+/// Size of PayloadHead on disk
+const PH_SIZE: usize = 8;
 
-pub fn parse_payloads(
-    reader: &mut BufReader<File>,
+impl PayloadHead {
+    fn decode(buf: &[u8; PH_SIZE]) -> Self {
+        let mut r = LeReader::new(buf);
+        Self {
+            magic: r.u16(),
+            index: r.u16(),
+            size: r.u32(),
+        }
+    }
+}
+
+/// LK_TRACE_PAYLOAD_MAGIC of qemu
+pub const PAYLOAD_MAGIC: u16 = 0xDCBA;
+
+/// Number of syscall arguments (a0-a6) which a payload can refer to.
+const MAX_PAYLOAD_INDEX: usize = 7;
+
+/// Borrowed view of a payload in mapped trace data.
+#[derive(Clone, Copy, Debug)]
+pub struct PayloadView<'a> {
+    pub index: usize,
+    /// Offset of data in data file
+    pub offset: u64,
+    pub data: &'a [u8],
+}
+
+/// Data of a payload, read from a file or left in mapped trace data.
+#[derive(Clone)]
+pub enum PayloadData {
+    Owned(Vec<u8>),
+    /// Refers to the map, which it keeps alive
+    Mapped(MappedBytes),
+}
+
+impl Default for PayloadData {
+    fn default() -> Self {
+        Self::Owned(vec![])
+    }
+}
+
+impl From<Vec<u8>> for PayloadData {
+    fn from(data: Vec<u8>) -> Self {
+        Self::Owned(data)
+    }
+}
+
+impl Deref for PayloadData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Owned(data) => data,
+            Self::Mapped(bytes) => bytes,
+        }
+    }
+}
+
+/// As bytes, wherever they are.
+impl Debug for PayloadData {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// Split payloads at offset from buf, which holds all payloads of
+/// an event and nothing else.
+pub fn payload_views(mut buf: &[u8], mut offset: u64) -> Result<Vec<PayloadView<'_>>> {
+    let mut ret = vec![];
+    while !buf.is_empty() {
+        let left = buf.len();
+        if left < PH_SIZE {
+            return Err(TraceError::PayloadOverrun { offset, size: PH_SIZE, left });
+        }
+        let head = PayloadHead::decode(buf[..PH_SIZE].try_into().unwrap());
+        check_payload(&head, offset, left)?;
+        let end = PH_SIZE + head.size as usize;
+        ret.push(PayloadView {
+            index: head.index as usize,
+            offset: offset + PH_SIZE as u64,
+            data: &buf[PH_SIZE..end],
+        });
+        buf = &buf[end..];
+        offset += end as u64;
+    }
+    Ok(ret)
+}
+
+/// Parse payloads at offset, which take up size bytes after event head.
+/// Each payload must fit in the rest of its event.
+pub fn parse_payloads<R: Read>(
+    reader: &mut R,
+    mut offset: u64,
     inout: u64,
     mut size: usize,
 ) -> Result<Vec<TracePayload>> {
-    assert!(size > PH_SIZE);
     let mut ret = vec![];
     while size > 0 {
-        let payload = parse_payload(reader, inout)?;
-        size -= PH_SIZE + payload.data.len();
+        let payload = parse_payload(reader, offset, inout, size)?;
+        let advance = PH_SIZE + payload.data.len();
+        offset += advance as u64;
+        size -= advance;
         ret.push(payload);
     }
     Ok(ret)
 }
 
-fn parse_payload(reader: &mut BufReader<File>, inout: u64) -> Result<TracePayload> {
+fn parse_payload<R: Read>(
+    reader: &mut R, offset: u64, inout: u64, left: usize
+) -> Result<TracePayload> {
+    if left < PH_SIZE {
+        return Err(TraceError::PayloadOverrun { offset, size: PH_SIZE, left });
+    }
     let mut buf = [0u8; PH_SIZE];
     reader.read_exact(&mut buf)?;
-    let head = unsafe { mem::transmute::<[u8; PH_SIZE], PayloadHead>(buf) };
-    let mut data = Vec::with_capacity(head.size as usize);
-    unsafe {
-        data.set_len(head.size as usize);
-    }
+    let head = PayloadHead::decode(&buf);
+    // Check size before allocation, it's garbage for a damaged payload.
+    check_payload(&head, offset, left)?;
+    let mut data = vec![0u8; head.size as usize];
     reader.read_exact(&mut data)?;
 
     Ok(TracePayload {
-        inout: inout,
+        inout,
         index: head.index as usize,
-        data,
+        data: data.into(),
     })
 }
+
+/// Check payload head at offset, left is the rest of its event.
+fn check_payload(head: &PayloadHead, offset: u64, left: usize) -> Result<()> {
+    if head.magic != PAYLOAD_MAGIC {
+        return Err(TraceError::BadPayloadMagic { offset, magic: head.magic });
+    }
+    let index = head.index as usize;
+    if index >= MAX_PAYLOAD_INDEX {
+        return Err(TraceError::UnknownPayload { offset, index });
+    }
+    let size = head.size as usize;
+    if size > left - PH_SIZE {
+        return Err(TraceError::PayloadOverrun { offset, size: PH_SIZE + size, left });
+    }
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use std::fmt::Display;
 use crate::sysno::*;
+use crate::bytes::LeReader;
 
 pub const SIG_BLOCK:    u64 = 0; // for blocking signals
 pub const SIG_UNBLOCK:  u64 = 1; // for unblocking signals
@@ -8,17 +9,35 @@ pub const SIG_SETMASK:  u64 = 2; // for setting the signal mask
 // Note: No restorer in sigaction for riscv64.
 #[derive(Copy, Clone, Default)]
 pub struct SigAction {
-    pub handler: usize,
-    pub flags: usize,
-    pub mask: usize,
+    pub handler: u64,
+    pub flags: u64,
+    pub mask: u64,
+}
+
+/// Size of struct sigaction of riscv64
+pub const SIGACTION_SIZE: usize = 24;
+
+impl SigAction {
+    /// Decode sigaction from buf, which holds at least SIGACTION_SIZE bytes.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < SIGACTION_SIZE {
+            return None;
+        }
+        let mut r = LeReader::new(buf);
+        Some(Self {
+            handler: r.u64(),
+            flags: r.u64(),
+            mask: r.u64(),
+        })
+    }
 }
 
 #[allow(dead_code)]
 pub const NSIG: usize = 64;
 
 /// signal action flags
-pub const SA_RESTORER: usize = 0x4000000;
-pub const SA_RESTART: usize = 0x10000000;
+pub const SA_RESTORER: u64 = 0x4000000;
+pub const SA_RESTART: u64 = 0x10000000;
 
 /*
  * if a blocked call to one of the following interfaces is
@@ -47,7 +66,7 @@ impl Display for SigAction {
     }
 }
 
-pub fn sa_flag_name(sa_flags: usize) -> String {
+pub fn sa_flag_name(sa_flags: u64) -> String {
     let mut names: Vec<String> = vec![];
     if sa_flags & SA_RESTART != 0 {
         names.push("SA_RESTART".to_string());
@@ -55,7 +74,7 @@ pub fn sa_flag_name(sa_flags: usize) -> String {
     if sa_flags & SA_RESTORER != 0 {
         names.push("SA_RESTORER".to_string());
     }
-    if names.len() > 0 {
+    if !names.is_empty() {
         names.join("|")
     } else {
         String::from("0x0")
@@ -0,0 +1,273 @@
+//! Signatures of syscalls: typed parameters and kind of result.
+//!
+//! A syscall is formatted from its signature by [`TraceEvent::syscall`],
+//! which text, JSON and level 2 output all use. Syscalls which aren't
+//! in the registry get as many plain integer parameters as they have.
+//!
+//! [`TraceEvent::syscall`]: crate::event::TraceEvent::syscall
+
+use crate::sysno::*;
+
+/// Kind of flags (or enum) argument, decoded by name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FlagsKind {
+    /// PROT_* of mmap and mprotect
+    Prot,
+    /// MAP_* of mmap
+    Map,
+    /// CLONE_* and exit signal of clone
+    Clone,
+    /// SIG_BLOCK, SIG_UNBLOCK or SIG_SETMASK of rt_sigprocmask
+    SigHow,
+    /// O_* of open, with access mode
+    Open,
+    /// File mode, in octal
+    Mode,
+    /// F_OK or R_OK, W_OK and X_OK of faccessat
+    Access,
+    /// AT_* of *at syscalls
+    At,
+}
+
+/// Kind of struct copied from user memory, as payload of its argument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StructKind {
+    /// struct stat
+    Stat,
+    /// struct new_utsname
+    Utsname,
+    /// struct sigaction
+    Sigaction,
+    /// sigset_t
+    Sigset,
+    /// Array of strings, e.g. argv, one payload per string
+    Strings,
+}
+
+/// Type of syscall parameter, which decides how it's formatted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Param {
+    /// Plain integer, in hex
+    Int,
+    /// File descriptor, in decimal
+    Fd,
+    /// Directory fd of *at syscalls, AT_FDCWD by name
+    Dirfd,
+    /// Path string, from payload
+    Path,
+    /// Flags of kind, by name
+    Flags(FlagsKind),
+    /// Signal number, by name
+    Signal,
+    /// Pid or tid in signed decimal, by seq-name at level 2
+    Pid,
+    /// User pointer, NULL if zero
+    Ptr,
+    /// Size or count, in decimal
+    Size,
+    /// Struct of kind, from payload
+    Struct(StructKind),
+    /// Buffer of read or write, as string if it's read from stdin or
+    /// written to stdout or stderr, and in payload
+    Buf,
+}
+
+/// Type of syscall result on success. Results in -4095..-1 are errors
+/// of any syscall, and are formatted by errno name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ret {
+    /// 0 on success, as OK
+    Status,
+    /// Plain integer, in hex
+    Int,
+    /// Number of bytes or entries, or an offset, in decimal
+    Count,
+    /// New file descriptor, in decimal
+    Fd,
+    /// Address, in hex
+    Addr,
+    /// Pid or tid in signed decimal, by seq-name at level 2
+    Pid,
+}
+
+/// Parameter of syscall. A labelled one is formatted as `name: value`.
+#[derive(Clone, Copy, Debug)]
+pub struct Arg {
+    pub name: &'static str,
+    pub param: Param,
+    pub labelled: bool,
+}
+
+const fn arg(name: &'static str, param: Param) -> Arg {
+    Arg { name, param, labelled: false }
+}
+
+const fn label(name: &'static str, param: Param) -> Arg {
+    Arg { name, param, labelled: true }
+}
+
+/// Signature of syscall.
+#[derive(Clone, Copy, Debug)]
+pub struct Signature {
+    pub sysno: u64,
+    /// Name of syscall, empty if it's unknown
+    pub name: &'static str,
+    pub args: &'static [Arg],
+    pub ret: Ret,
+}
+
+const fn sig(sysno: u64, name: &'static str, args: &'static [Arg], ret: Ret) -> Signature {
+    Signature { sysno, name, args, ret }
+}
+
+use FlagsKind::*;
+use Param::*;
+use StructKind::*;
+
+/// Syscalls with typed parameters, ordered by number.
+const SIGNATURES: &[Signature] = &[
+    sig(SYS_GETCWD, "getcwd", &[arg("buf", Path), arg("size", Size)], Ret::Count),
+    sig(SYS_DUP, "dup", &[arg("oldfd", Fd)], Ret::Fd),
+    sig(SYS_DUP3, "dup3", &[arg("oldfd", Fd), arg("newfd", Fd), arg("flags", Int)], Ret::Fd),
+    sig(SYS_FCNTL, "fcntl", &[arg("fd", Fd), arg("cmd", Int), arg("arg", Int)], Ret::Int),
+    sig(SYS_IOCTL, "ioctl", &[arg("fd", Fd), arg("cmd", Int), arg("arg", Int)], Ret::Int),
+    sig(SYS_MKDIRAT, "mkdirat", &[
+        arg("dirfd", Dirfd), arg("pathname", Path), arg("mode", Flags(Mode)),
+    ], Ret::Status),
+    sig(SYS_UNLINKAT, "unlinkat", &[
+        arg("dirfd", Dirfd), arg("pathname", Path), arg("flags", Flags(At)),
+    ], Ret::Status),
+    sig(SYS_MOUNT, "mount", &[
+        arg("source", Path), arg("target", Path), arg("filesystemtype", Path),
+        arg("mountflags", Int), arg("data", Ptr),
+    ], Ret::Status),
+    sig(SYS_FTRUNCATE, "ftruncate", &[arg("fd", Fd), arg("length", Size)], Ret::Status),
+    // For faccessat, there're 3 args, NO 'flags'.
+    // For faccessat2, there're 4 args with 'flags'.
+    sig(SYS_FACCESSAT, "faccessat", &[
+        arg("dirfd", Dirfd), arg("pathname", Path), arg("mode", Flags(Access)),
+    ], Ret::Status),
+    sig(SYS_CHDIR, "chdir", &[arg("path", Path)], Ret::Status),
+    sig(SYS_FCHDIR, "fchdir", &[arg("fd", Fd)], Ret::Status),
+    sig(SYS_FCHMOD, "fchmod", &[arg("fd", Fd), arg("mode", Flags(Mode))], Ret::Status),
+    // For fchmodat, there're 3 args, NO 'flags', as for faccessat.
+    sig(SYS_FCHMODAT, "fchmodat", &[
+        arg("dirfd", Dirfd), arg("pathname", Path), arg("mode", Flags(Mode)),
+    ], Ret::Status),
+    sig(SYS_FCHOWNAT, "fchownat", &[
+        arg("dirfd", Dirfd), arg("pathname", Path), arg("owner", Int), arg("group", Int),
+        arg("flags", Flags(At)),
+    ], Ret::Status),
+    sig(SYS_FCHOWN, "fchown", &[arg("fd", Fd), arg("owner", Int), arg("group", Int)], Ret::Status),
+    sig(SYS_OPENAT, "openat", &[
+        arg("dirfd", Dirfd), arg("pathname", Path), arg("flags", Flags(Open)), arg("mode", Flags(Mode)),
+    ], Ret::Fd),
+    sig(SYS_CLOSE, "close", &[arg("fd", Fd)], Ret::Status),
+    sig(SYS_PIPE2, "pipe2", &[arg("pipefd", Ptr), arg("flags", Int)], Ret::Status),
+    sig(SYS_GETDENTS64, "getdents64", &[arg("fd", Fd), arg("dirp", Ptr), arg("count", Size)], Ret::Count),
+    sig(SYS_LSEEK, "lseek", &[arg("fd", Fd), arg("offset", Int), arg("whence", Int)], Ret::Count),
+    sig(SYS_READ, "read", &[arg("fd", Fd), arg("buf", Buf), arg("count", Size)], Ret::Count),
+    sig(SYS_WRITE, "write", &[arg("fd", Fd), arg("buf", Buf), arg("count", Size)], Ret::Count),
+    sig(SYS_READV, "readv", &[arg("fd", Fd), arg("iov", Ptr), arg("iovcnt", Size)], Ret::Count),
+    sig(SYS_WRITEV, "writev", &[arg("fd", Fd), arg("iov", Ptr), arg("iovcnt", Size)], Ret::Count),
+    sig(SYS_PREAD64, "pread64", &[
+        arg("fd", Fd), arg("buf", Ptr), arg("count", Size), arg("offset", Int),
+    ], Ret::Count),
+    sig(SYS_PWRITE64, "pwrite64", &[
+        arg("fd", Fd), arg("buf", Ptr), arg("count", Size), arg("offset", Int),
+    ], Ret::Count),
+    sig(SYS_SENDFILE, "sendfile", &[
+        arg("out_fd", Fd), arg("in_fd", Fd), arg("offset", Ptr), arg("count", Size),
+    ], Ret::Count),
+    sig(SYS_READLINKAT, "readlinkat", &[
+        arg("dirfd", Dirfd), arg("pathname", Path), arg("buf", Ptr), arg("bufsiz", Size),
+    ], Ret::Count),
+    sig(SYS_FSTATAT, "fstatat", &[
+        arg("dirfd", Dirfd), arg("pathname", Path), arg("statbuf", Struct(Stat)), arg("flags", Flags(At)),
+    ], Ret::Status),
+    sig(SYS_FSTAT, "fstat", &[arg("fd", Fd), arg("statbuf", Struct(Stat))], Ret::Status),
+    sig(SYS_EXIT, "exit", &[arg("status", Int)], Ret::Status),
+    sig(SYS_EXIT_GROUP, "exit_group", &[arg("status", Int)], Ret::Status),
+    sig(SYS_SET_TID_ADDRESS, "set_tid_address", &[arg("tidptr", Ptr)], Ret::Pid),
+    sig(SYS_SET_ROBUST_LIST, "set_robust_list", &[arg("head", Ptr), arg("len", Size)], Ret::Status),
+    sig(SYS_CLOCK_GETTIME, "clock_gettime", &[arg("clockid", Int), arg("tp", Ptr)], Ret::Status),
+    sig(SYS_KILL, "kill", &[arg("pid", Pid), arg("sig", Signal)], Ret::Status),
+    sig(SYS_TKILL, "tkill", &[arg("tid", Pid), arg("sig", Signal)], Ret::Status),
+    sig(SYS_TGKILL, "tgkill", &[arg("tgid", Pid), arg("tid", Pid), arg("sig", Signal)], Ret::Status),
+    sig(SYS_RT_SIGACTION, "rt_sigaction", &[
+        arg("signum", Signal), arg("act", Struct(Sigaction)), arg("oldact", Struct(Sigaction)),
+        arg("sigsetsize", Size),
+    ], Ret::Status),
+    sig(SYS_RT_SIGPROCMASK, "rt_sigprocmask", &[
+        arg("how", Flags(SigHow)), label("nset", Struct(Sigset)), label("oset", Struct(Sigset)),
+        arg("sigsetsize", Size),
+    ], Ret::Status),
+    sig(SYS_SETPGID, "setpgid", &[arg("pid", Pid), arg("pgid", Pid)], Ret::Status),
+    sig(SYS_GETPGID, "getpgid", &[arg("pid", Pid)], Ret::Pid),
+    sig(SYS_UNAME, "uname", &[arg("buf", Struct(Utsname))], Ret::Status),
+    sig(SYS_GETPID, "getpid", &[], Ret::Pid),
+    sig(SYS_GETPPID, "getppid", &[], Ret::Pid),
+    sig(SYS_GETUID, "getuid", &[], Ret::Int),
+    sig(SYS_GETEUID, "geteuid", &[], Ret::Int),
+    sig(SYS_GETGID, "getgid", &[], Ret::Int),
+    sig(SYS_GETEGID, "getegid", &[], Ret::Int),
+    sig(SYS_GETTID, "gettid", &[], Ret::Pid),
+    sig(SYS_BRK, "brk", &[arg("addr", Ptr)], Ret::Addr),
+    sig(SYS_MUNMAP, "munmap", &[arg("addr", Ptr), arg("length", Size)], Ret::Status),
+    sig(SYS_CLONE, "clone", &[
+        arg("flags", Flags(Clone)), label("stack", Ptr), label("parent_tid", Ptr), label("tls", Ptr),
+        label("child_tid", Ptr),
+    ], Ret::Pid),
+    sig(SYS_EXECVE, "execve", &[
+        arg("pathname", Path), arg("argv", Struct(Strings)), arg("envp", Struct(Strings)),
+    ], Ret::Status),
+    sig(SYS_MMAP, "mmap", &[
+        arg("addr", Ptr), arg("length", Size), arg("prot", Flags(Prot)), arg("flags", Flags(Map)),
+        arg("fd", Fd), arg("offset", Int),
+    ], Ret::Addr),
+    sig(SYS_MPROTECT, "mprotect", &[arg("addr", Ptr), arg("len", Size), arg("prot", Flags(Prot))], Ret::Status),
+    sig(SYS_MSYNC, "msync", &[arg("addr", Ptr), arg("length", Size), arg("flags", Int)], Ret::Status),
+    sig(SYS_MADVISE, "madvise", &[arg("addr", Ptr), arg("length", Size), arg("advice", Int)], Ret::Status),
+    sig(SYS_WAIT4, "wait4", &[
+        arg("pid", Pid), arg("wstatus", Ptr), arg("options", Int), arg("rusage", Ptr),
+    ], Ret::Pid),
+    sig(SYS_PRLIMIT64, "prlimit64", &[
+        arg("pid", Pid), arg("resource", Int), arg("new_limit", Ptr), arg("old_limit", Ptr),
+    ], Ret::Status),
+    sig(SYS_GETRANDOM, "getrandom", &[arg("buf", Ptr), arg("buflen", Size), arg("flags", Int)], Ret::Count),
+];
+
+/// Parameters of syscalls which aren't in the registry.
+const INTS: [Arg; 7] = [
+    arg("arg0", Int), arg("arg1", Int), arg("arg2", Int), arg("arg3", Int),
+    arg("arg4", Int), arg("arg5", Int), arg("arg6", Int),
+];
+
+/// Signature of syscall. A syscall which isn't in the registry has as
+/// many integer parameters as it takes, or all 7 if it's unknown.
+pub fn signature(sysno: u64) -> Signature {
+    if let Ok(idx) = SIGNATURES.binary_search_by_key(&sysno, |s| s.sysno) {
+        return SIGNATURES[idx];
+    }
+    match syscall_info(sysno) {
+        Some((name, argc)) => sig(sysno, name, &INTS[..argc], Ret::Status),
+        None => sig(sysno, "", &INTS, Ret::Int),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_sorted_by_sysno() {
+        assert!(SIGNATURES.windows(2).all(|w| w[0].sysno < w[1].sysno));
+    }
+
+    #[test]
+    fn signatures_named_as_syscalls() {
+        for sig in SIGNATURES {
+            assert_eq!(syscall_info(sig.sysno), Some((sig.name, sig.args.len())), "{}", sig.name);
+        }
+    }
+}
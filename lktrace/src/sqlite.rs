@@ -0,0 +1,335 @@
+//! Load of analysis results into a SQLite database, for ad-hoc SQL
+//! queries such as which openat calls failed with ENOENT.
+//!
+//! Tables:
+//! - `tasks(id, task, sscratch, satp, first_seq, leader)`: one row per
+//!   task from its first event to its exit, so a sscratch reused by the
+//!   kernel has a row for each task. Satp is of its first event of its
+//!   own, not the copy of its parent's clone request (the parent's satp
+//!   if it has none), and leader is the
+//!   id of the first task of its thread group in trace
+//! - `clones(parent, child, parent_id, child_id, seq, flags)`: clone
+//!   edges, seq is the number of the clone request in file. Parent_id is
+//!   null if the parent isn't in trace
+//! - `events(id, task, task_id, idx, seq, reply_seq, offset, satp, sysno,
+//!   name, result, ret, errno, signal, signal_stage, usp)`: events of
+//!   tasks after grouping, idx is the index of event in its task. A cloned
+//!   task starts at idx 1, as its copy of the parent's clone request
+//!   is only in `clones`
+//! - `args(event, idx, name, raw, value)`: raw and decoded arguments,
+//!   with names of parameters
+//! - `payloads(event, idx, inout, data)`: payloads of events
+//!
+//! Task names depend on level as in text output, e.g. seq-names at
+//! level 2. Ids and addresses are stored as 64-bit signed integers.
+
+use std::collections::HashMap;
+use rusqlite::{params, Connection, Transaction};
+use crate::{Options, OUT};
+use crate::clone::{cloned_by, CLONE_THREAD};
+use crate::errno::{errno_name, is_error};
+use crate::error::Result;
+use crate::event::{SeqNames, SigStage, TraceFlow};
+use crate::level1;
+use crate::signal::sig_name;
+
+const SCHEMA: &str = "
+DROP TABLE IF EXISTS tasks;
+DROP TABLE IF EXISTS clones;
+DROP TABLE IF EXISTS events;
+DROP TABLE IF EXISTS args;
+DROP TABLE IF EXISTS payloads;
+CREATE TABLE tasks (
+    id INTEGER PRIMARY KEY,
+    task TEXT NOT NULL,
+    sscratch INTEGER NOT NULL,
+    satp INTEGER NOT NULL,
+    first_seq INTEGER NOT NULL,
+    leader INTEGER NOT NULL REFERENCES tasks(id)
+);
+CREATE TABLE clones (
+    parent TEXT NOT NULL,
+    child TEXT NOT NULL,
+    parent_id INTEGER REFERENCES tasks(id),
+    child_id INTEGER NOT NULL REFERENCES tasks(id),
+    seq INTEGER NOT NULL,
+    flags INTEGER NOT NULL
+);
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY,
+    task TEXT NOT NULL,
+    task_id INTEGER NOT NULL REFERENCES tasks(id),
+    idx INTEGER NOT NULL,
+    seq INTEGER NOT NULL,
+    reply_seq INTEGER,
+    offset INTEGER NOT NULL,
+    satp INTEGER NOT NULL,
+    sysno INTEGER,
+    name TEXT,
+    result INTEGER,
+    ret TEXT,
+    errno TEXT,
+    signal TEXT,
+    signal_stage TEXT,
+    usp INTEGER NOT NULL
+);
+CREATE TABLE args (
+    event INTEGER NOT NULL REFERENCES events(id),
+    idx INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    raw INTEGER NOT NULL,
+    value TEXT NOT NULL
+);
+CREATE TABLE payloads (
+    event INTEGER NOT NULL REFERENCES events(id),
+    idx INTEGER NOT NULL,
+    inout TEXT NOT NULL,
+    data BLOB NOT NULL
+);
+";
+
+/// Indexes are created after loading, which is faster.
+const INDEXES: &str = "
+CREATE INDEX tasks_task ON tasks(task);
+CREATE INDEX events_task ON events(task);
+CREATE INDEX events_name ON events(name);
+CREATE INDEX args_event ON args(event);
+CREATE INDEX payloads_event ON payloads(event);
+";
+
+/// Row of a task in `tasks`, written after all flows, when its leader
+/// is known.
+struct TaskRow {
+    tid: u64,
+    first_seq: u64,
+    /// Satp of its first event, which is its parent's for a cloned task
+    first_satp: u64,
+    /// Satp of its first event of its own
+    satp: Option<u64>,
+    /// Parent, number of clone request and its flags
+    clone: Option<(u64, u64, u64)>,
+}
+
+/// Rows of tasks, a new one each time a sscratch starts a task.
+#[derive(Default)]
+struct TaskRows {
+    rows: Vec<TaskRow>,
+    /// Row of the live task of each sscratch
+    live: HashMap<u64, usize>,
+}
+
+impl TaskRows {
+    /// Row of the task of flow, which is added for its first flow.
+    fn row(&mut self, tid: u64, flow: &TraceFlow) -> Option<usize> {
+        if flow.flushed == 0 {
+            let first = flow.events.first()?;
+            self.rows.push(TaskRow {
+                tid,
+                first_seq: first.seq,
+                first_satp: first.head.satp,
+                satp: None,
+                clone: None,
+            });
+            self.live.insert(tid, self.rows.len() - 1);
+        }
+        self.live.get(&tid).copied()
+    }
+
+    /// Row of parent of each task, i.e. the last task of its sscratch
+    /// that started by the clone.
+    fn parents(&self) -> Vec<Option<usize>> {
+        let mut by_tid: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (idx, row) in self.rows.iter().enumerate() {
+            by_tid.entry(row.tid).or_default().push(idx);
+        }
+        by_tid.values_mut().for_each(|rows| rows.sort_by_key(|idx| self.rows[*idx].first_seq));
+        self.rows.iter().map(|row| {
+            let (parent, seq, _) = row.clone?;
+            let rows = by_tid.get(&parent)?;
+            rows.iter().rev().copied().find(|idx| self.rows[*idx].first_seq <= seq)
+        }).collect()
+    }
+
+    /// Write rows of tasks and clones, ids are 1-based indexes of rows.
+    fn insert(&self, tx: &Transaction, names: &SeqNames) -> Result<()> {
+        let parents = self.parents();
+        let mut insert_task = tx.prepare("INSERT INTO tasks VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+        let mut insert_clone = tx.prepare("INSERT INTO clones VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+        for (idx, row) in self.rows.iter().enumerate() {
+            // Bounded, in case of a damaged trace
+            let mut leader = idx;
+            for _ in 0..self.rows.len() {
+                match (self.rows[leader].clone, parents[leader]) {
+                    (Some((_, _, flags)), Some(parent)) if flags & CLONE_THREAD != 0 => leader = parent,
+                    _ => break,
+                }
+            }
+            let satp = row.satp.unwrap_or(row.first_satp);
+            insert_task.execute(params![
+                idx as i64 + 1, names.task(row.tid), row.tid as i64, satp as i64,
+                row.first_seq as i64, leader as i64 + 1,
+            ])?;
+            if let Some((parent, seq, flags)) = row.clone {
+                insert_clone.execute(params![
+                    names.task(parent), names.task(row.tid),
+                    parents[idx].map(|parent| parent as i64 + 1), idx as i64 + 1,
+                    seq as i64, flags as i64,
+                ])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write events of task.
+fn insert_flow(
+    tx: &Transaction,
+    tasks: &mut TaskRows,
+    tid: u64,
+    flow: &TraceFlow,
+    names: &SeqNames,
+) -> Result<()> {
+    let task = names.task(tid);
+    let Some(row) = tasks.row(tid, flow) else {
+        return Ok(());
+    };
+    let task_id = row as i64 + 1;
+    // A new task starts with a copy of its parent's clone request,
+    // which is an event of the parent
+    let parent = cloned_by(tid, flow);
+    if let Some(parent) = parent {
+        let first = &flow.events[0];
+        tasks.rows[row].clone = Some((parent, first.seq, first.head.ax[0]));
+    }
+
+    let mut insert_event = tx.prepare_cached(
+        "INSERT INTO events (task, task_id, idx, seq, reply_seq, offset, satp, sysno,
+             name, result, ret, errno, signal, signal_stage, usp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+    )?;
+    let mut insert_arg = tx.prepare_cached("INSERT INTO args VALUES (?1, ?2, ?3, ?4, ?5)")?;
+    let mut insert_payload = tx.prepare_cached("INSERT INTO payloads VALUES (?1, ?2, ?3, ?4)")?;
+    for (idx, evt) in flow.events.iter().enumerate().skip(parent.is_some() as usize) {
+        let (signal, stage) = match evt.signal {
+            SigStage::Empty => (None, None),
+            SigStage::Enter(signo) => (Some(sig_name(signo)), Some("enter")),
+            SigStage::Exit(signo) => (Some(sig_name(signo)), Some("exit")),
+        };
+        let idx = (flow.flushed + idx) as i64;
+        let reply_seq = evt.reply_seq.map(|seq| seq as i64);
+        // Signal delivery isn't a syscall, its head is empty
+        if let SigStage::Enter(_) = evt.signal {
+            insert_event.execute(params![
+                task, task_id, idx, evt.seq as i64, reply_seq, evt.offset as i64,
+                evt.head.satp as i64, None::<i64>, None::<String>, None::<i64>,
+                None::<String>, None::<String>, signal, stage, evt.head.usp as i64,
+            ])?;
+            continue;
+        }
+
+        let satp = &mut tasks.rows[row].satp;
+        satp.get_or_insert(evt.head.satp);
+        let Some(call) = evt.syscall() else {
+            continue;
+        };
+        let errno = is_error(evt.result).then(|| errno_name(evt.result));
+        insert_event.execute(params![
+            task, task_id, idx, evt.seq as i64, reply_seq, evt.offset as i64,
+            evt.head.satp as i64, evt.head.ax[7] as i64, call.name, evt.result,
+            call.ret, errno, signal, stage, evt.head.usp as i64,
+        ])?;
+        let id = tx.last_insert_rowid();
+        for (i, (value, name)) in call.args.iter().zip(&call.arg_names).enumerate() {
+            insert_arg.execute(params![id, i as i64, name, evt.head.ax[i] as i64, value])?;
+        }
+        for payload in &evt.payloads {
+            let inout = if payload.inout == OUT { "out" } else { "in" };
+            insert_payload.execute(params![id, payload.index as i64, inout, &payload.data[..]])?;
+        }
+    }
+    Ok(())
+}
+
+/// Group events of trace data file by task as level 1 does, and load
+/// them into the database at output. Tables of a previous load are
+/// replaced. Leaders of thread groups are known after all tasks.
+pub fn export(path: &str, output: &str, opts: &Options) -> Result<()> {
+    let mut conn = Connection::open(output)?;
+    conn.execute_batch(SCHEMA)?;
+    let tx = conn.transaction()?;
+    let mut tasks = TaskRows::default();
+    let names = SeqNames::new(opts.level);
+    let grouped = level1::group(path, opts, &names, |tid, flow| {
+        insert_flow(&tx, &mut tasks, tid, &flow, &names)
+    })?;
+    tasks.insert(&tx, &names)?;
+    tx.execute_batch(INDEXES)?;
+    tx.commit()?;
+    info!("{} tasks loaded into {}", grouped.task_seq.len(), output);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IN;
+    use crate::event::tests::{task_record, TempTrace};
+    use crate::sysno::*;
+
+    const PARENT: u64 = 0x1000;
+    const CHILD: u64 = 0x2000;
+    const PARENT_SATP: u64 = 0x8000000000081234;
+    const CHILD_SATP: u64 = 0x8000000000085555;
+
+    /// Raw event of task in address space satp.
+    fn event(tid: u64, inout: u64, sysno: u64, ax0: u64, satp: u64) -> Vec<u8> {
+        let mut buf = task_record(tid, inout, [ax0, 0, 0, 0, 0, 0, 0, sysno], &[]);
+        // After magic and sizes, inout, cause, epc, ax, usp, stack and
+        // orig_a0
+        buf[176..184].copy_from_slice(&satp.to_le_bytes());
+        buf
+    }
+
+    /// Clone of child by parent, which runs first.
+    fn clone(data: &mut Vec<u8>, flags: u64, satp: u64) {
+        data.extend(event(PARENT, IN, SYS_CLONE, flags, PARENT_SATP));
+        data.extend(event(CHILD, OUT, SYS_CLONE, 0, satp));
+        data.extend(event(PARENT, OUT, SYS_CLONE, 5, PARENT_SATP));
+    }
+
+    #[test]
+    fn reused_sscratch_has_rows() {
+        // Parent forks a child, which exits, then its sscratch is
+        // reused by a thread of parent
+        let mut data = vec![];
+        clone(&mut data, 17, CHILD_SATP);
+        data.extend(event(CHILD, IN, SYS_GETPID, 0, CHILD_SATP));
+        data.extend(event(CHILD, OUT, SYS_GETPID, 5, CHILD_SATP));
+        data.extend(event(CHILD, IN, SYS_EXIT_GROUP, 0, CHILD_SATP));
+        clone(&mut data, 0x10f00, PARENT_SATP);
+        data.extend(event(CHILD, IN, SYS_EXIT, 0, PARENT_SATP));
+        data.extend(event(PARENT, IN, SYS_EXIT_GROUP, 0, PARENT_SATP));
+        let trace = TempTrace::new("sqlite", &data);
+        let db = format!("{}.db", trace.path());
+        let opts = Options { level: 1, ..Default::default() };
+        export(trace.path(), &db, &opts).unwrap();
+
+        let conn = Connection::open(&db).unwrap();
+        let rows = |sql: &str| -> Vec<Vec<i64>> {
+            let mut stmt = conn.prepare(sql).unwrap();
+            let cols = stmt.column_count();
+            stmt.query_map([], |row| (0..cols).map(|i| row.get(i)).collect())
+                .unwrap().map(|row| row.unwrap()).collect()
+        };
+        // Tasks in order of exit: forked child, thread and parent
+        assert_eq!(rows("SELECT id, sscratch, satp, first_seq, leader FROM tasks"), [
+            [1, CHILD as i64, CHILD_SATP as i64, 0, 1],
+            [2, CHILD as i64, PARENT_SATP as i64, 6, 3],
+            [3, PARENT as i64, PARENT_SATP as i64, 0, 3],
+        ]);
+        assert_eq!(rows("SELECT parent_id, child_id, seq FROM clones"), [[3, 1, 0], [3, 2, 6]]);
+        assert_eq!(rows("SELECT task_id, count(*) FROM events GROUP BY task_id"), [[1, 2], [2, 1], [3, 3]]);
+        drop(conn);
+        std::fs::remove_file(db).unwrap();
+    }
+}
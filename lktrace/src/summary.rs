@@ -0,0 +1,335 @@
+//! Statistics of syscalls, like `strace -c`.
+//!
+//! For each syscall, count its calls and its errors by errno, over the
+//! whole trace or per task, thread group or address space (satp).
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use serde_json::json;
+use crate::clone::{cloned_by, ThreadGroups};
+use crate::errno::{errno_name, is_error};
+use crate::event::{SeqNames, SigStage, TraceFlow};
+use crate::format::Format;
+
+/// What a summary table covers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scope {
+    /// One table of the whole trace
+    #[default]
+    All,
+    /// One table per task (sscratch)
+    Task,
+    /// One table per thread group, titled by its leader
+    Process,
+    /// One table per address space (satp)
+    Pid,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Scope::All),
+            "task" => Ok(Scope::Task),
+            "process" => Ok(Scope::Process),
+            "pid" => Ok(Scope::Pid),
+            _ => Err(format!("unknown summary scope {}, expect all, task, process or pid", s)),
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Scope::All => write!(f, "all"),
+            Scope::Task => write!(f, "task"),
+            Scope::Process => write!(f, "process"),
+            Scope::Pid => write!(f, "pid"),
+        }
+    }
+}
+
+/// Counts of a syscall.
+#[derive(Clone, Debug, Default)]
+pub struct Counts {
+    pub calls: usize,
+    /// Errors by errno name
+    pub errors: BTreeMap<Cow<'static, str>, usize>,
+}
+
+impl Counts {
+    pub fn error_count(&self) -> usize {
+        self.errors.values().sum()
+    }
+}
+
+/// Counts of syscalls by name in one scope.
+type Table = BTreeMap<String, Counts>;
+
+/// Statistics of syscalls, fed with flows of tasks.
+#[derive(Default)]
+pub struct Summary {
+    scope: Scope,
+    /// Tables in order of first appearance of their task or satp
+    tables: Vec<(u64, Table)>,
+    index: HashMap<u64, usize>,
+    /// Thread groups of tasks, whose tables are merged when printed
+    groups: ThreadGroups,
+}
+
+impl Summary {
+    pub fn new(scope: Scope) -> Self {
+        Self { scope, ..Default::default() }
+    }
+
+    fn table(&mut self, key: u64) -> &mut Table {
+        let idx = *self.index.entry(key).or_insert_with(|| {
+            self.tables.push((key, Table::new()));
+            self.tables.len() - 1
+        });
+        &mut self.tables[idx].1
+    }
+
+    /// Count completed syscalls of task.
+    pub fn add(&mut self, tid: u64, flow: &TraceFlow) {
+        if self.scope == Scope::Process {
+            self.groups.add(tid, flow);
+        }
+        // A new task starts with a copy of its parent's clone request,
+        // which is counted in the parent
+        let skip = cloned_by(tid, flow).is_some() as usize;
+        for evt in &flow.events[skip..] {
+            // Signal delivery isn't a syscall
+            if let SigStage::Enter(_) = evt.signal {
+                continue;
+            }
+            let key = match self.scope {
+                Scope::All => 0,
+                Scope::Task | Scope::Process => tid,
+                Scope::Pid => evt.head.satp,
+            };
+            let Some(call) = evt.syscall() else {
+                continue;
+            };
+            let counts = self.table(key).entry(call.name).or_default();
+            counts.calls += 1;
+            // Syscall interrupted by signal has no result
+            if is_error(evt.result) && !matches!(evt.signal, SigStage::Exit(_)) {
+                *counts.errors.entry(errno_name(evt.result)).or_default() += 1;
+            }
+        }
+    }
+
+    fn title(&self, key: u64, names: &SeqNames) -> String {
+        match self.scope {
+            Scope::All => "all".to_string(),
+            Scope::Task | Scope::Process => names.task(key),
+            Scope::Pid => format!("{:#x}", key),
+        }
+    }
+
+    /// Print tables in format, with tasks named by names.
+    pub fn print(&self, format: Format, names: &SeqNames) {
+        let merged;
+        let tables = if self.scope == Scope::Process {
+            merged = self.by_group();
+            &merged
+        } else {
+            &self.tables
+        };
+        for (key, table) in tables {
+            let title = self.title(*key, names);
+            match format {
+                Format::Jsonl => println!("{}", table_json(self.scope, &title, table)),
+                Format::Text | Format::Strace => print_table(self.scope, &title, table),
+            }
+        }
+    }
+
+    /// Tables of tasks merged by thread group, in order of first
+    /// appearance of group.
+    fn by_group(&self) -> Vec<(u64, Table)> {
+        let mut tables: Vec<(u64, Table)> = vec![];
+        let mut index = HashMap::new();
+        for (tid, table) in &self.tables {
+            let leader = self.groups.leader(*tid);
+            let idx = *index.entry(leader).or_insert_with(|| {
+                tables.push((leader, Table::new()));
+                tables.len() - 1
+            });
+            for (name, counts) in table {
+                let merged = tables[idx].1.entry(name.clone()).or_default();
+                merged.calls += counts.calls;
+                for (errno, n) in &counts.errors {
+                    *merged.errors.entry(errno.clone()).or_default() += n;
+                }
+            }
+        }
+        tables
+    }
+}
+
+/// Syscalls of table, most called first.
+fn sorted(table: &Table) -> Vec<(&String, &Counts)> {
+    let mut rows = table.iter().collect::<Vec<_>>();
+    rows.sort_by(|a, b| b.1.calls.cmp(&a.1.calls).then_with(|| a.0.cmp(b.0)));
+    rows
+}
+
+fn print_table(scope: Scope, title: &str, table: &Table) {
+    let total = table.values().map(|c| c.calls).sum::<usize>();
+    match scope {
+        Scope::All => println!("Summary:"),
+        _ => println!("Summary of {} {}:", scope, title),
+    }
+    println!("{:>7} {:>9} {:>9} {:<20} errnos", "% calls", "calls", "errors", "syscall");
+    println!("{:-<7} {:-<9} {:-<9} {:-<20} {:-<16}", "", "", "", "", "");
+    for (name, counts) in sorted(table) {
+        let errnos = counts.errors
+            .iter()
+            .map(|(errno, n)| format!("{}: {}", errno, n))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let row = format!("{:>7.2} {:>9} {:>9} {:<20} {}",
+            counts.calls as f64 * 100.0 / total as f64,
+            counts.calls, counts.error_count(), name, errnos);
+        println!("{}", row.trim_end());
+    }
+    println!("{:-<7} {:-<9} {:-<9} {:-<20} {:-<16}", "", "", "", "", "");
+    let errors = table.values().map(|c| c.error_count()).sum::<usize>();
+    println!("{:>7.2} {:>9} {:>9} total", 100.0, total, errors);
+    println!();
+}
+
+fn table_json(scope: Scope, title: &str, table: &Table) -> serde_json::Value {
+    let total = table.values().map(|c| c.calls).sum::<usize>();
+    let syscalls = sorted(table)
+        .into_iter()
+        .map(|(name, counts)| json!({
+            "name": name,
+            "calls": counts.calls,
+            "errors": counts.error_count(),
+            "errnos": counts.errors,
+            "percent": counts.calls as f64 * 100.0 / total as f64,
+        }))
+        .collect::<Vec<_>>();
+    json!({
+        "type": "summary",
+        "scope": scope.to_string(),
+        "of": title,
+        "calls": total,
+        "syscalls": syscalls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errno::{EACCES, EINTR, ENOENT};
+    use crate::event::{TraceEvent, USER_ECALL};
+    use crate::sysno::*;
+
+    const PARENT: u64 = 0x1000;
+    const THREAD: u64 = 0x2000;
+    const CHILD: u64 = 0x3000;
+    const PARENT_SATP: u64 = 0x8000000000081234;
+    const CHILD_SATP: u64 = 0x8000000000085555;
+
+    /// Completed syscall of task, whose first argument is arg.
+    fn event(tid: u64, satp: u64, sysno: u64, arg: u64, result: i64) -> TraceEvent {
+        let mut evt = TraceEvent { level: 1, result, ..Default::default() };
+        evt.head.cause = USER_ECALL;
+        evt.head.ax[0] = arg;
+        evt.head.ax[7] = sysno;
+        evt.head.sscratch = tid;
+        evt.head.satp = satp;
+        evt
+    }
+
+    fn flow(events: Vec<TraceEvent>) -> TraceFlow {
+        TraceFlow { events, ..TraceFlow::new() }
+    }
+
+    /// Parent clones a thread and forks a child, which start with its
+    /// clone requests. Flows come as tasks exit.
+    fn workload(scope: Scope) -> Summary {
+        let thread_clone = event(PARENT, PARENT_SATP, SYS_CLONE, 0x10f00, 0);
+        let fork = event(PARENT, PARENT_SATP, SYS_CLONE, 17, 0);
+        let mut interrupted = event(PARENT, PARENT_SATP, SYS_READ, 0, -EINTR as i64);
+        interrupted.signal = SigStage::Exit(10);
+
+        let mut summary = Summary::new(scope);
+        summary.add(THREAD, &flow(vec![
+            thread_clone.clone(),
+            event(THREAD, PARENT_SATP, SYS_OPENAT, 0, -ENOENT as i64),
+            event(THREAD, PARENT_SATP, SYS_EXIT, 0, 0),
+        ]));
+        summary.add(CHILD, &flow(vec![
+            fork.clone(),
+            event(CHILD, CHILD_SATP, SYS_GETPID, 0, 5),
+            event(CHILD, CHILD_SATP, SYS_EXIT_GROUP, 0, 0),
+        ]));
+        summary.add(PARENT, &flow(vec![
+            event(PARENT, PARENT_SATP, SYS_OPENAT, 0, -ENOENT as i64),
+            event(PARENT, PARENT_SATP, SYS_OPENAT, 0, 3),
+            event(PARENT, PARENT_SATP, SYS_OPENAT, 0, -EACCES as i64),
+            thread_clone,
+            fork,
+            interrupted,
+            event(PARENT, PARENT_SATP, SYS_EXIT_GROUP, 0, 0),
+        ]));
+        summary
+    }
+
+    /// Calls of syscall in table, and its errors by errno.
+    fn counts(table: &Table, name: &str) -> (usize, Vec<(String, usize)>) {
+        let counts = table.get(name).cloned().unwrap_or_default();
+        let errors = counts.errors.iter().map(|(errno, n)| (errno.to_string(), *n)).collect();
+        (counts.calls, errors)
+    }
+
+    fn errors(errnos: &[(&str, usize)]) -> Vec<(String, usize)> {
+        errnos.iter().map(|(errno, n)| (errno.to_string(), *n)).collect()
+    }
+
+    #[test]
+    fn counts_calls_and_errors() {
+        let summary = workload(Scope::All);
+        assert_eq!(summary.tables.len(), 1);
+        let table = &summary.tables[0].1;
+        assert_eq!(counts(table, "openat"), (4, errors(&[("EACCES", 1), ("ENOENT", 2)])));
+        // Clone requests copied into children aren't counted again, and
+        // an interrupted syscall has no error
+        assert_eq!(counts(table, "clone"), (2, vec![]));
+        assert_eq!(counts(table, "read"), (1, vec![]));
+        assert_eq!(table.values().map(|c| c.calls).sum::<usize>(), 11);
+        assert_eq!(table.values().map(Counts::error_count).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn groups_by_scope() {
+        let keys = |tables: &[(u64, Table)]| tables.iter().map(|(key, _)| *key).collect::<Vec<_>>();
+
+        let summary = workload(Scope::Task);
+        assert_eq!(keys(&summary.tables), [THREAD, CHILD, PARENT]);
+        assert_eq!(counts(&summary.tables[0].1, "openat"), (1, errors(&[("ENOENT", 1)])));
+        assert_eq!(counts(&summary.tables[0].1, "clone"), (0, vec![]));
+
+        // Thread is merged into its leader, the forked child isn't
+        let summary = workload(Scope::Process);
+        let tables = summary.by_group();
+        assert_eq!(keys(&tables), [PARENT, CHILD]);
+        assert_eq!(counts(&tables[0].1, "openat"), (4, errors(&[("EACCES", 1), ("ENOENT", 2)])));
+        assert_eq!(counts(&tables[0].1, "exit"), (1, vec![]));
+        assert_eq!(counts(&tables[1].1, "getpid"), (1, vec![]));
+
+        let summary = workload(Scope::Pid);
+        assert_eq!(keys(&summary.tables), [PARENT_SATP, CHILD_SATP]);
+        assert_eq!(counts(&summary.tables[1].1, "getpid"), (1, vec![]));
+        assert_eq!(counts(&summary.tables[1].1, "exit_group"), (1, vec![]));
+        assert_eq!(counts(&summary.tables[0].1, "exit_group"), (1, vec![]));
+    }
+}
@@ -1,44 +1,180 @@
-///
-/// Linux syscall
-///
+//!
+//! Linux syscall numbers of riscv64, which are of asm-generic.
+//!
+//! Numbers which riscv64 doesn't use (e.g. renameat, time32 syscalls)
+//! are left out.
+//!
 
+pub const SYS_IO_SETUP: u64 = 0x0;
+pub const SYS_IO_DESTROY: u64 = 0x1;
+pub const SYS_IO_SUBMIT: u64 = 0x2;
+pub const SYS_IO_CANCEL: u64 = 0x3;
+pub const SYS_IO_GETEVENTS: u64 = 0x4;
+pub const SYS_SETXATTR: u64 = 0x5;
+pub const SYS_LSETXATTR: u64 = 0x6;
+pub const SYS_FSETXATTR: u64 = 0x7;
+pub const SYS_GETXATTR: u64 = 0x8;
+pub const SYS_LGETXATTR: u64 = 0x9;
+pub const SYS_FGETXATTR: u64 = 0xa;
+pub const SYS_LISTXATTR: u64 = 0xb;
+pub const SYS_LLISTXATTR: u64 = 0xc;
+pub const SYS_FLISTXATTR: u64 = 0xd;
+pub const SYS_REMOVEXATTR: u64 = 0xe;
+pub const SYS_LREMOVEXATTR: u64 = 0xf;
+pub const SYS_FREMOVEXATTR: u64 = 0x10;
 pub const SYS_GETCWD: u64 = 0x11;
+pub const SYS_LOOKUP_DCOOKIE: u64 = 0x12;
+pub const SYS_EVENTFD2: u64 = 0x13;
+pub const SYS_EPOLL_CREATE1: u64 = 0x14;
+pub const SYS_EPOLL_CTL: u64 = 0x15;
+pub const SYS_EPOLL_PWAIT: u64 = 0x16;
+pub const SYS_DUP: u64 = 0x17;
 pub const SYS_DUP3: u64 = 0x18;
 pub const SYS_FCNTL: u64 = 0x19;
+pub const SYS_INOTIFY_INIT1: u64 = 0x1a;
+pub const SYS_INOTIFY_ADD_WATCH: u64 = 0x1b;
+pub const SYS_INOTIFY_RM_WATCH: u64 = 0x1c;
 pub const SYS_IOCTL: u64 = 0x1d;
+pub const SYS_IOPRIO_SET: u64 = 0x1e;
+pub const SYS_IOPRIO_GET: u64 = 0x1f;
+pub const SYS_FLOCK: u64 = 0x20;
+pub const SYS_MKNODAT: u64 = 0x21;
 pub const SYS_MKDIRAT: u64 = 0x22;
 pub const SYS_UNLINKAT: u64 = 0x23;
+pub const SYS_SYMLINKAT: u64 = 0x24;
+pub const SYS_LINKAT: u64 = 0x25;
+pub const SYS_UMOUNT2: u64 = 0x27;
 pub const SYS_MOUNT: u64 = 0x28;
-//pub const SYS_FTRUNCATE: u64 = 0x2e;
+pub const SYS_PIVOT_ROOT: u64 = 0x29;
+pub const SYS_STATFS: u64 = 0x2b;
+pub const SYS_FSTATFS: u64 = 0x2c;
+pub const SYS_TRUNCATE: u64 = 0x2d;
+pub const SYS_FTRUNCATE: u64 = 0x2e;
+pub const SYS_FALLOCATE: u64 = 0x2f;
 pub const SYS_FACCESSAT: u64 = 0x30;
 pub const SYS_CHDIR: u64 = 0x31;
-//pub const SYS_FCHMOD: u64 = 0x34;
+pub const SYS_FCHDIR: u64 = 0x32;
+pub const SYS_CHROOT: u64 = 0x33;
+pub const SYS_FCHMOD: u64 = 0x34;
 pub const SYS_FCHMODAT: u64 = 0x35;
 pub const SYS_FCHOWNAT: u64 = 0x36;
+pub const SYS_FCHOWN: u64 = 0x37;
 pub const SYS_OPENAT: u64 = 0x38;
 pub const SYS_CLOSE: u64 = 0x39;
+pub const SYS_VHANGUP: u64 = 0x3a;
+pub const SYS_PIPE2: u64 = 0x3b;
+pub const SYS_QUOTACTL: u64 = 0x3c;
 pub const SYS_GETDENTS64: u64 = 0x3d;
 pub const SYS_LSEEK: u64 = 0x3e;
 pub const SYS_READ: u64 = 0x3f;
 pub const SYS_WRITE: u64 = 0x40;
+pub const SYS_READV: u64 = 0x41;
 pub const SYS_WRITEV: u64 = 0x42;
-//pub const SYS_PREAD64: u64 = 0x43;
+pub const SYS_PREAD64: u64 = 0x43;
+pub const SYS_PWRITE64: u64 = 0x44;
+pub const SYS_PREADV: u64 = 0x45;
+pub const SYS_PWRITEV: u64 = 0x46;
 pub const SYS_SENDFILE: u64 = 0x47;
-//pub const SYS_READLINKAT: u64 = 0x4e;
+pub const SYS_PSELECT6: u64 = 0x48;
+pub const SYS_PPOLL: u64 = 0x49;
+pub const SYS_SIGNALFD4: u64 = 0x4a;
+pub const SYS_VMSPLICE: u64 = 0x4b;
+pub const SYS_SPLICE: u64 = 0x4c;
+pub const SYS_TEE: u64 = 0x4d;
+pub const SYS_READLINKAT: u64 = 0x4e;
 pub const SYS_FSTATAT: u64 = 0x4f;
-//pub const SYS_CAPGET: u64 = 0x5a;
-//pub const SYS_EXIT: u64 = 0x5d;
+pub const SYS_FSTAT: u64 = 0x50;
+pub const SYS_SYNC: u64 = 0x51;
+pub const SYS_FSYNC: u64 = 0x52;
+pub const SYS_FDATASYNC: u64 = 0x53;
+pub const SYS_SYNC_FILE_RANGE: u64 = 0x54;
+pub const SYS_TIMERFD_CREATE: u64 = 0x55;
+pub const SYS_TIMERFD_SETTIME: u64 = 0x56;
+pub const SYS_TIMERFD_GETTIME: u64 = 0x57;
+pub const SYS_UTIMENSAT: u64 = 0x58;
+pub const SYS_ACCT: u64 = 0x59;
+pub const SYS_CAPGET: u64 = 0x5a;
+pub const SYS_CAPSET: u64 = 0x5b;
+pub const SYS_PERSONALITY: u64 = 0x5c;
+pub const SYS_EXIT: u64 = 0x5d;
 pub const SYS_EXIT_GROUP: u64 = 0x5e;
-//pub const SYS_SETITIMER: u64 = 0x67;
-
+pub const SYS_WAITID: u64 = 0x5f;
+pub const SYS_SET_TID_ADDRESS: u64 = 0x60;
+pub const SYS_UNSHARE: u64 = 0x61;
+pub const SYS_FUTEX: u64 = 0x62;
+pub const SYS_SET_ROBUST_LIST: u64 = 0x63;
+pub const SYS_GET_ROBUST_LIST: u64 = 0x64;
+pub const SYS_NANOSLEEP: u64 = 0x65;
+pub const SYS_GETITIMER: u64 = 0x66;
+pub const SYS_SETITIMER: u64 = 0x67;
+pub const SYS_KEXEC_LOAD: u64 = 0x68;
+pub const SYS_INIT_MODULE: u64 = 0x69;
+pub const SYS_DELETE_MODULE: u64 = 0x6a;
+pub const SYS_TIMER_CREATE: u64 = 0x6b;
+pub const SYS_TIMER_GETTIME: u64 = 0x6c;
+pub const SYS_TIMER_GETOVERRUN: u64 = 0x6d;
+pub const SYS_TIMER_SETTIME: u64 = 0x6e;
+pub const SYS_TIMER_DELETE: u64 = 0x6f;
+pub const SYS_CLOCK_SETTIME: u64 = 0x70;
+pub const SYS_CLOCK_GETTIME: u64 = 0x71;
+pub const SYS_CLOCK_GETRES: u64 = 0x72;
+pub const SYS_CLOCK_NANOSLEEP: u64 = 0x73;
+pub const SYS_SYSLOG: u64 = 0x74;
+pub const SYS_PTRACE: u64 = 0x75;
+pub const SYS_SCHED_SETPARAM: u64 = 0x76;
+pub const SYS_SCHED_SETSCHEDULER: u64 = 0x77;
+pub const SYS_SCHED_GETSCHEDULER: u64 = 0x78;
+pub const SYS_SCHED_GETPARAM: u64 = 0x79;
+pub const SYS_SCHED_SETAFFINITY: u64 = 0x7a;
+pub const SYS_SCHED_GETAFFINITY: u64 = 0x7b;
+pub const SYS_SCHED_YIELD: u64 = 0x7c;
+pub const SYS_SCHED_GET_PRIORITY_MAX: u64 = 0x7d;
+pub const SYS_SCHED_GET_PRIORITY_MIN: u64 = 0x7e;
+pub const SYS_SCHED_RR_GET_INTERVAL: u64 = 0x7f;
+pub const SYS_RESTART_SYSCALL: u64 = 0x80;
 pub const SYS_KILL: u64 = 0x81;
+pub const SYS_TKILL: u64 = 0x82;
 pub const SYS_TGKILL: u64 = 0x83;
+pub const SYS_SIGALTSTACK: u64 = 0x84;
+pub const SYS_RT_SIGSUSPEND: u64 = 0x85;
 pub const SYS_RT_SIGACTION: u64 = 0x86;
 pub const SYS_RT_SIGPROCMASK: u64 = 0x87;
+pub const SYS_RT_SIGPENDING: u64 = 0x88;
+pub const SYS_RT_SIGTIMEDWAIT: u64 = 0x89;
+pub const SYS_RT_SIGQUEUEINFO: u64 = 0x8a;
 pub const SYS_RT_SIGRETURN: u64 = 0x8b;
-
-//pub const SYS_SETPGID: u64 = 0x9a;
+pub const SYS_SETPRIORITY: u64 = 0x8c;
+pub const SYS_GETPRIORITY: u64 = 0x8d;
+pub const SYS_REBOOT: u64 = 0x8e;
+pub const SYS_SETREGID: u64 = 0x8f;
+pub const SYS_SETGID: u64 = 0x90;
+pub const SYS_SETREUID: u64 = 0x91;
+pub const SYS_SETUID: u64 = 0x92;
+pub const SYS_SETRESUID: u64 = 0x93;
+pub const SYS_GETRESUID: u64 = 0x94;
+pub const SYS_SETRESGID: u64 = 0x95;
+pub const SYS_GETRESGID: u64 = 0x96;
+pub const SYS_SETFSUID: u64 = 0x97;
+pub const SYS_SETFSGID: u64 = 0x98;
+pub const SYS_TIMES: u64 = 0x99;
+pub const SYS_SETPGID: u64 = 0x9a;
+pub const SYS_GETPGID: u64 = 0x9b;
+pub const SYS_GETSID: u64 = 0x9c;
+pub const SYS_SETSID: u64 = 0x9d;
+pub const SYS_GETGROUPS: u64 = 0x9e;
+pub const SYS_SETGROUPS: u64 = 0x9f;
 pub const SYS_UNAME: u64 = 0xa0;
+pub const SYS_SETHOSTNAME: u64 = 0xa1;
+pub const SYS_SETDOMAINNAME: u64 = 0xa2;
+pub const SYS_GETRLIMIT: u64 = 0xa3;
+pub const SYS_SETRLIMIT: u64 = 0xa4;
+pub const SYS_GETRUSAGE: u64 = 0xa5;
+pub const SYS_UMASK: u64 = 0xa6;
+pub const SYS_PRCTL: u64 = 0xa7;
+pub const SYS_GETCPU: u64 = 0xa8;
+pub const SYS_GETTIMEOFDAY: u64 = 0xa9;
+pub const SYS_SETTIMEOFDAY: u64 = 0xaa;
+pub const SYS_ADJTIMEX: u64 = 0xab;
 pub const SYS_GETPID: u64 = 0xac;
 pub const SYS_GETPPID: u64 = 0xad;
 pub const SYS_GETUID: u64 = 0xae;
@@ -46,25 +182,476 @@ pub const SYS_GETEUID: u64 = 0xaf;
 pub const SYS_GETGID: u64 = 0xb0;
 pub const SYS_GETEGID: u64 = 0xb1;
 pub const SYS_GETTID: u64 = 0xb2;
+pub const SYS_SYSINFO: u64 = 0xb3;
+pub const SYS_MQ_OPEN: u64 = 0xb4;
+pub const SYS_MQ_UNLINK: u64 = 0xb5;
+pub const SYS_MQ_TIMEDSEND: u64 = 0xb6;
+pub const SYS_MQ_TIMEDRECEIVE: u64 = 0xb7;
+pub const SYS_MQ_NOTIFY: u64 = 0xb8;
+pub const SYS_MQ_GETSETATTR: u64 = 0xb9;
+pub const SYS_MSGGET: u64 = 0xba;
+pub const SYS_MSGCTL: u64 = 0xbb;
+pub const SYS_MSGRCV: u64 = 0xbc;
+pub const SYS_MSGSND: u64 = 0xbd;
+pub const SYS_SEMGET: u64 = 0xbe;
+pub const SYS_SEMCTL: u64 = 0xbf;
+pub const SYS_SEMTIMEDOP: u64 = 0xc0;
+pub const SYS_SEMOP: u64 = 0xc1;
+pub const SYS_SHMGET: u64 = 0xc2;
+pub const SYS_SHMCTL: u64 = 0xc3;
+pub const SYS_SHMAT: u64 = 0xc4;
+pub const SYS_SHMDT: u64 = 0xc5;
+pub const SYS_SOCKET: u64 = 0xc6;
+pub const SYS_SOCKETPAIR: u64 = 0xc7;
+pub const SYS_BIND: u64 = 0xc8;
+pub const SYS_LISTEN: u64 = 0xc9;
+pub const SYS_ACCEPT: u64 = 0xca;
+pub const SYS_CONNECT: u64 = 0xcb;
+pub const SYS_GETSOCKNAME: u64 = 0xcc;
+pub const SYS_GETPEERNAME: u64 = 0xcd;
+pub const SYS_SENDTO: u64 = 0xce;
+pub const SYS_RECVFROM: u64 = 0xcf;
+pub const SYS_SETSOCKOPT: u64 = 0xd0;
+pub const SYS_GETSOCKOPT: u64 = 0xd1;
+pub const SYS_SHUTDOWN: u64 = 0xd2;
+pub const SYS_SENDMSG: u64 = 0xd3;
+pub const SYS_RECVMSG: u64 = 0xd4;
+pub const SYS_READAHEAD: u64 = 0xd5;
 pub const SYS_BRK: u64 = 0xd6;
 pub const SYS_MUNMAP: u64 = 0xd7;
+pub const SYS_MREMAP: u64 = 0xd8;
+pub const SYS_ADD_KEY: u64 = 0xd9;
+pub const SYS_REQUEST_KEY: u64 = 0xda;
+pub const SYS_KEYCTL: u64 = 0xdb;
 pub const SYS_CLONE: u64 = 0xdc;
 pub const SYS_EXECVE: u64 = 0xdd;
-
 pub const SYS_MMAP: u64 = 0xde;
+pub const SYS_FADVISE64: u64 = 0xdf;
+pub const SYS_SWAPON: u64 = 0xe0;
+pub const SYS_SWAPOFF: u64 = 0xe1;
 pub const SYS_MPROTECT: u64 = 0xe2;
 pub const SYS_MSYNC: u64 = 0xe3;
-//pub const SYS_MADVISE: u64 = 0xe9;
+pub const SYS_MLOCK: u64 = 0xe4;
+pub const SYS_MUNLOCK: u64 = 0xe5;
+pub const SYS_MLOCKALL: u64 = 0xe6;
+pub const SYS_MUNLOCKALL: u64 = 0xe7;
+pub const SYS_MINCORE: u64 = 0xe8;
+pub const SYS_MADVISE: u64 = 0xe9;
+pub const SYS_REMAP_FILE_PAGES: u64 = 0xea;
+pub const SYS_MBIND: u64 = 0xeb;
+pub const SYS_GET_MEMPOLICY: u64 = 0xec;
+pub const SYS_SET_MEMPOLICY: u64 = 0xed;
+pub const SYS_MIGRATE_PAGES: u64 = 0xee;
+pub const SYS_MOVE_PAGES: u64 = 0xef;
+pub const SYS_RT_TGSIGQUEUEINFO: u64 = 0xf0;
+pub const SYS_PERF_EVENT_OPEN: u64 = 0xf1;
+pub const SYS_ACCEPT4: u64 = 0xf2;
+pub const SYS_RECVMMSG: u64 = 0xf3;
+pub const SYS_RISCV_HWPROBE: u64 = 0x102;
+pub const SYS_RISCV_FLUSH_ICACHE: u64 = 0x103;
 pub const SYS_WAIT4: u64 = 0x104;
 pub const SYS_PRLIMIT64: u64 = 0x105;
+pub const SYS_FANOTIFY_INIT: u64 = 0x106;
+pub const SYS_FANOTIFY_MARK: u64 = 0x107;
+pub const SYS_NAME_TO_HANDLE_AT: u64 = 0x108;
+pub const SYS_OPEN_BY_HANDLE_AT: u64 = 0x109;
+pub const SYS_CLOCK_ADJTIME: u64 = 0x10a;
+pub const SYS_SYNCFS: u64 = 0x10b;
+pub const SYS_SETNS: u64 = 0x10c;
+pub const SYS_SENDMMSG: u64 = 0x10d;
+pub const SYS_PROCESS_VM_READV: u64 = 0x10e;
+pub const SYS_PROCESS_VM_WRITEV: u64 = 0x10f;
+pub const SYS_KCMP: u64 = 0x110;
+pub const SYS_FINIT_MODULE: u64 = 0x111;
+pub const SYS_SCHED_SETATTR: u64 = 0x112;
+pub const SYS_SCHED_GETATTR: u64 = 0x113;
+pub const SYS_RENAMEAT2: u64 = 0x114;
+pub const SYS_SECCOMP: u64 = 0x115;
 pub const SYS_GETRANDOM: u64 = 0x116;
-//pub const SYS_RSEQ: u64 = 0x125;
-
-pub const SYS_SET_TID_ADDRESS: u64 = 0x60;
-pub const SYS_SET_ROBUST_LIST: u64 = 0x63;
-pub const SYS_CLOCK_GETTIME: u64 = 0x71;
-//pub const SYS_CLOCK_NANOSLEEP: u64 = 0x73;
-//pub const SYS_SCHED_GETAFFINITY: u64 = 0x7b;
-//
+pub const SYS_MEMFD_CREATE: u64 = 0x117;
+pub const SYS_BPF: u64 = 0x118;
+pub const SYS_EXECVEAT: u64 = 0x119;
+pub const SYS_USERFAULTFD: u64 = 0x11a;
+pub const SYS_MEMBARRIER: u64 = 0x11b;
+pub const SYS_MLOCK2: u64 = 0x11c;
+pub const SYS_COPY_FILE_RANGE: u64 = 0x11d;
+pub const SYS_PREADV2: u64 = 0x11e;
+pub const SYS_PWRITEV2: u64 = 0x11f;
+pub const SYS_PKEY_MPROTECT: u64 = 0x120;
+pub const SYS_PKEY_ALLOC: u64 = 0x121;
+pub const SYS_PKEY_FREE: u64 = 0x122;
+pub const SYS_STATX: u64 = 0x123;
+pub const SYS_IO_PGETEVENTS: u64 = 0x124;
+pub const SYS_RSEQ: u64 = 0x125;
+pub const SYS_KEXEC_FILE_LOAD: u64 = 0x126;
+pub const SYS_PIDFD_SEND_SIGNAL: u64 = 0x1a8;
+pub const SYS_IO_URING_SETUP: u64 = 0x1a9;
+pub const SYS_IO_URING_ENTER: u64 = 0x1aa;
+pub const SYS_IO_URING_REGISTER: u64 = 0x1ab;
+pub const SYS_OPEN_TREE: u64 = 0x1ac;
+pub const SYS_MOVE_MOUNT: u64 = 0x1ad;
+pub const SYS_FSOPEN: u64 = 0x1ae;
+pub const SYS_FSCONFIG: u64 = 0x1af;
+pub const SYS_FSMOUNT: u64 = 0x1b0;
+pub const SYS_FSPICK: u64 = 0x1b1;
+pub const SYS_PIDFD_OPEN: u64 = 0x1b2;
+pub const SYS_CLONE3: u64 = 0x1b3;
+pub const SYS_CLOSE_RANGE: u64 = 0x1b4;
+pub const SYS_OPENAT2: u64 = 0x1b5;
+pub const SYS_PIDFD_GETFD: u64 = 0x1b6;
+pub const SYS_FACCESSAT2: u64 = 0x1b7;
+pub const SYS_PROCESS_MADVISE: u64 = 0x1b8;
+pub const SYS_EPOLL_PWAIT2: u64 = 0x1b9;
+pub const SYS_MOUNT_SETATTR: u64 = 0x1ba;
+pub const SYS_QUOTACTL_FD: u64 = 0x1bb;
+pub const SYS_LANDLOCK_CREATE_RULESET: u64 = 0x1bc;
+pub const SYS_LANDLOCK_ADD_RULE: u64 = 0x1bd;
+pub const SYS_LANDLOCK_RESTRICT_SELF: u64 = 0x1be;
+pub const SYS_MEMFD_SECRET: u64 = 0x1bf;
+pub const SYS_PROCESS_MRELEASE: u64 = 0x1c0;
+pub const SYS_FUTEX_WAITV: u64 = 0x1c1;
+pub const SYS_SET_MEMPOLICY_HOME_NODE: u64 = 0x1c2;
 
 pub const MAX_SYSCALL_NBR: u64 = 451;
+
+/// Syscalls with names and numbers of arguments, ordered by number.
+const SYSCALLS: [(u64, &str, usize); 306] = [
+    (SYS_IO_SETUP, "io_setup", 2),
+    (SYS_IO_DESTROY, "io_destroy", 1),
+    (SYS_IO_SUBMIT, "io_submit", 3),
+    (SYS_IO_CANCEL, "io_cancel", 3),
+    (SYS_IO_GETEVENTS, "io_getevents", 5),
+    (SYS_SETXATTR, "setxattr", 5),
+    (SYS_LSETXATTR, "lsetxattr", 5),
+    (SYS_FSETXATTR, "fsetxattr", 5),
+    (SYS_GETXATTR, "getxattr", 4),
+    (SYS_LGETXATTR, "lgetxattr", 4),
+    (SYS_FGETXATTR, "fgetxattr", 4),
+    (SYS_LISTXATTR, "listxattr", 3),
+    (SYS_LLISTXATTR, "llistxattr", 3),
+    (SYS_FLISTXATTR, "flistxattr", 3),
+    (SYS_REMOVEXATTR, "removexattr", 2),
+    (SYS_LREMOVEXATTR, "lremovexattr", 2),
+    (SYS_FREMOVEXATTR, "fremovexattr", 2),
+    (SYS_GETCWD, "getcwd", 2),
+    (SYS_LOOKUP_DCOOKIE, "lookup_dcookie", 3),
+    (SYS_EVENTFD2, "eventfd2", 2),
+    (SYS_EPOLL_CREATE1, "epoll_create1", 1),
+    (SYS_EPOLL_CTL, "epoll_ctl", 4),
+    (SYS_EPOLL_PWAIT, "epoll_pwait", 6),
+    (SYS_DUP, "dup", 1),
+    (SYS_DUP3, "dup3", 3),
+    (SYS_FCNTL, "fcntl", 3),
+    (SYS_INOTIFY_INIT1, "inotify_init1", 1),
+    (SYS_INOTIFY_ADD_WATCH, "inotify_add_watch", 3),
+    (SYS_INOTIFY_RM_WATCH, "inotify_rm_watch", 2),
+    (SYS_IOCTL, "ioctl", 3),
+    (SYS_IOPRIO_SET, "ioprio_set", 3),
+    (SYS_IOPRIO_GET, "ioprio_get", 2),
+    (SYS_FLOCK, "flock", 2),
+    (SYS_MKNODAT, "mknodat", 4),
+    (SYS_MKDIRAT, "mkdirat", 3),
+    (SYS_UNLINKAT, "unlinkat", 3),
+    (SYS_SYMLINKAT, "symlinkat", 3),
+    (SYS_LINKAT, "linkat", 5),
+    (SYS_UMOUNT2, "umount2", 2),
+    (SYS_MOUNT, "mount", 5),
+    (SYS_PIVOT_ROOT, "pivot_root", 2),
+    (SYS_STATFS, "statfs", 2),
+    (SYS_FSTATFS, "fstatfs", 2),
+    (SYS_TRUNCATE, "truncate", 2),
+    (SYS_FTRUNCATE, "ftruncate", 2),
+    (SYS_FALLOCATE, "fallocate", 4),
+    (SYS_FACCESSAT, "faccessat", 3),
+    (SYS_CHDIR, "chdir", 1),
+    (SYS_FCHDIR, "fchdir", 1),
+    (SYS_CHROOT, "chroot", 1),
+    (SYS_FCHMOD, "fchmod", 2),
+    (SYS_FCHMODAT, "fchmodat", 3),
+    (SYS_FCHOWNAT, "fchownat", 5),
+    (SYS_FCHOWN, "fchown", 3),
+    (SYS_OPENAT, "openat", 4),
+    (SYS_CLOSE, "close", 1),
+    (SYS_VHANGUP, "vhangup", 0),
+    (SYS_PIPE2, "pipe2", 2),
+    (SYS_QUOTACTL, "quotactl", 4),
+    (SYS_GETDENTS64, "getdents64", 3),
+    (SYS_LSEEK, "lseek", 3),
+    (SYS_READ, "read", 3),
+    (SYS_WRITE, "write", 3),
+    (SYS_READV, "readv", 3),
+    (SYS_WRITEV, "writev", 3),
+    (SYS_PREAD64, "pread64", 4),
+    (SYS_PWRITE64, "pwrite64", 4),
+    (SYS_PREADV, "preadv", 5),
+    (SYS_PWRITEV, "pwritev", 5),
+    (SYS_SENDFILE, "sendfile", 4),
+    (SYS_PSELECT6, "pselect6", 6),
+    (SYS_PPOLL, "ppoll", 5),
+    (SYS_SIGNALFD4, "signalfd4", 4),
+    (SYS_VMSPLICE, "vmsplice", 4),
+    (SYS_SPLICE, "splice", 6),
+    (SYS_TEE, "tee", 4),
+    (SYS_READLINKAT, "readlinkat", 4),
+    (SYS_FSTATAT, "fstatat", 4),
+    (SYS_FSTAT, "fstat", 2),
+    (SYS_SYNC, "sync", 0),
+    (SYS_FSYNC, "fsync", 1),
+    (SYS_FDATASYNC, "fdatasync", 1),
+    (SYS_SYNC_FILE_RANGE, "sync_file_range", 4),
+    (SYS_TIMERFD_CREATE, "timerfd_create", 2),
+    (SYS_TIMERFD_SETTIME, "timerfd_settime", 4),
+    (SYS_TIMERFD_GETTIME, "timerfd_gettime", 2),
+    (SYS_UTIMENSAT, "utimensat", 4),
+    (SYS_ACCT, "acct", 1),
+    (SYS_CAPGET, "capget", 2),
+    (SYS_CAPSET, "capset", 2),
+    (SYS_PERSONALITY, "personality", 1),
+    (SYS_EXIT, "exit", 1),
+    (SYS_EXIT_GROUP, "exit_group", 1),
+    (SYS_WAITID, "waitid", 5),
+    (SYS_SET_TID_ADDRESS, "set_tid_address", 1),
+    (SYS_UNSHARE, "unshare", 1),
+    (SYS_FUTEX, "futex", 6),
+    (SYS_SET_ROBUST_LIST, "set_robust_list", 2),
+    (SYS_GET_ROBUST_LIST, "get_robust_list", 3),
+    (SYS_NANOSLEEP, "nanosleep", 2),
+    (SYS_GETITIMER, "getitimer", 2),
+    (SYS_SETITIMER, "setitimer", 3),
+    (SYS_KEXEC_LOAD, "kexec_load", 4),
+    (SYS_INIT_MODULE, "init_module", 3),
+    (SYS_DELETE_MODULE, "delete_module", 2),
+    (SYS_TIMER_CREATE, "timer_create", 3),
+    (SYS_TIMER_GETTIME, "timer_gettime", 2),
+    (SYS_TIMER_GETOVERRUN, "timer_getoverrun", 1),
+    (SYS_TIMER_SETTIME, "timer_settime", 4),
+    (SYS_TIMER_DELETE, "timer_delete", 1),
+    (SYS_CLOCK_SETTIME, "clock_settime", 2),
+    (SYS_CLOCK_GETTIME, "clock_gettime", 2),
+    (SYS_CLOCK_GETRES, "clock_getres", 2),
+    (SYS_CLOCK_NANOSLEEP, "clock_nanosleep", 4),
+    (SYS_SYSLOG, "syslog", 3),
+    (SYS_PTRACE, "ptrace", 4),
+    (SYS_SCHED_SETPARAM, "sched_setparam", 2),
+    (SYS_SCHED_SETSCHEDULER, "sched_setscheduler", 3),
+    (SYS_SCHED_GETSCHEDULER, "sched_getscheduler", 1),
+    (SYS_SCHED_GETPARAM, "sched_getparam", 2),
+    (SYS_SCHED_SETAFFINITY, "sched_setaffinity", 3),
+    (SYS_SCHED_GETAFFINITY, "sched_getaffinity", 3),
+    (SYS_SCHED_YIELD, "sched_yield", 0),
+    (SYS_SCHED_GET_PRIORITY_MAX, "sched_get_priority_max", 1),
+    (SYS_SCHED_GET_PRIORITY_MIN, "sched_get_priority_min", 1),
+    (SYS_SCHED_RR_GET_INTERVAL, "sched_rr_get_interval", 2),
+    (SYS_RESTART_SYSCALL, "restart_syscall", 0),
+    (SYS_KILL, "kill", 2),
+    (SYS_TKILL, "tkill", 2),
+    (SYS_TGKILL, "tgkill", 3),
+    (SYS_SIGALTSTACK, "sigaltstack", 2),
+    (SYS_RT_SIGSUSPEND, "rt_sigsuspend", 2),
+    (SYS_RT_SIGACTION, "rt_sigaction", 4),
+    (SYS_RT_SIGPROCMASK, "rt_sigprocmask", 4),
+    (SYS_RT_SIGPENDING, "rt_sigpending", 2),
+    (SYS_RT_SIGTIMEDWAIT, "rt_sigtimedwait", 4),
+    (SYS_RT_SIGQUEUEINFO, "rt_sigqueueinfo", 3),
+    (SYS_RT_SIGRETURN, "rt_sigreturn", 0),
+    (SYS_SETPRIORITY, "setpriority", 3),
+    (SYS_GETPRIORITY, "getpriority", 2),
+    (SYS_REBOOT, "reboot", 4),
+    (SYS_SETREGID, "setregid", 2),
+    (SYS_SETGID, "setgid", 1),
+    (SYS_SETREUID, "setreuid", 2),
+    (SYS_SETUID, "setuid", 1),
+    (SYS_SETRESUID, "setresuid", 3),
+    (SYS_GETRESUID, "getresuid", 3),
+    (SYS_SETRESGID, "setresgid", 3),
+    (SYS_GETRESGID, "getresgid", 3),
+    (SYS_SETFSUID, "setfsuid", 1),
+    (SYS_SETFSGID, "setfsgid", 1),
+    (SYS_TIMES, "times", 1),
+    (SYS_SETPGID, "setpgid", 2),
+    (SYS_GETPGID, "getpgid", 1),
+    (SYS_GETSID, "getsid", 1),
+    (SYS_SETSID, "setsid", 0),
+    (SYS_GETGROUPS, "getgroups", 2),
+    (SYS_SETGROUPS, "setgroups", 2),
+    (SYS_UNAME, "uname", 1),
+    (SYS_SETHOSTNAME, "sethostname", 2),
+    (SYS_SETDOMAINNAME, "setdomainname", 2),
+    (SYS_GETRLIMIT, "getrlimit", 2),
+    (SYS_SETRLIMIT, "setrlimit", 2),
+    (SYS_GETRUSAGE, "getrusage", 2),
+    (SYS_UMASK, "umask", 1),
+    (SYS_PRCTL, "prctl", 5),
+    (SYS_GETCPU, "getcpu", 3),
+    (SYS_GETTIMEOFDAY, "gettimeofday", 2),
+    (SYS_SETTIMEOFDAY, "settimeofday", 2),
+    (SYS_ADJTIMEX, "adjtimex", 1),
+    (SYS_GETPID, "getpid", 0),
+    (SYS_GETPPID, "getppid", 0),
+    (SYS_GETUID, "getuid", 0),
+    (SYS_GETEUID, "geteuid", 0),
+    (SYS_GETGID, "getgid", 0),
+    (SYS_GETEGID, "getegid", 0),
+    (SYS_GETTID, "gettid", 0),
+    (SYS_SYSINFO, "sysinfo", 1),
+    (SYS_MQ_OPEN, "mq_open", 4),
+    (SYS_MQ_UNLINK, "mq_unlink", 1),
+    (SYS_MQ_TIMEDSEND, "mq_timedsend", 5),
+    (SYS_MQ_TIMEDRECEIVE, "mq_timedreceive", 5),
+    (SYS_MQ_NOTIFY, "mq_notify", 2),
+    (SYS_MQ_GETSETATTR, "mq_getsetattr", 3),
+    (SYS_MSGGET, "msgget", 2),
+    (SYS_MSGCTL, "msgctl", 3),
+    (SYS_MSGRCV, "msgrcv", 5),
+    (SYS_MSGSND, "msgsnd", 4),
+    (SYS_SEMGET, "semget", 3),
+    (SYS_SEMCTL, "semctl", 4),
+    (SYS_SEMTIMEDOP, "semtimedop", 4),
+    (SYS_SEMOP, "semop", 3),
+    (SYS_SHMGET, "shmget", 3),
+    (SYS_SHMCTL, "shmctl", 3),
+    (SYS_SHMAT, "shmat", 3),
+    (SYS_SHMDT, "shmdt", 1),
+    (SYS_SOCKET, "socket", 3),
+    (SYS_SOCKETPAIR, "socketpair", 4),
+    (SYS_BIND, "bind", 3),
+    (SYS_LISTEN, "listen", 2),
+    (SYS_ACCEPT, "accept", 3),
+    (SYS_CONNECT, "connect", 3),
+    (SYS_GETSOCKNAME, "getsockname", 3),
+    (SYS_GETPEERNAME, "getpeername", 3),
+    (SYS_SENDTO, "sendto", 6),
+    (SYS_RECVFROM, "recvfrom", 6),
+    (SYS_SETSOCKOPT, "setsockopt", 5),
+    (SYS_GETSOCKOPT, "getsockopt", 5),
+    (SYS_SHUTDOWN, "shutdown", 2),
+    (SYS_SENDMSG, "sendmsg", 3),
+    (SYS_RECVMSG, "recvmsg", 3),
+    (SYS_READAHEAD, "readahead", 3),
+    (SYS_BRK, "brk", 1),
+    (SYS_MUNMAP, "munmap", 2),
+    (SYS_MREMAP, "mremap", 5),
+    (SYS_ADD_KEY, "add_key", 5),
+    (SYS_REQUEST_KEY, "request_key", 4),
+    (SYS_KEYCTL, "keyctl", 5),
+    (SYS_CLONE, "clone", 5),
+    (SYS_EXECVE, "execve", 3),
+    (SYS_MMAP, "mmap", 6),
+    (SYS_FADVISE64, "fadvise64", 4),
+    (SYS_SWAPON, "swapon", 2),
+    (SYS_SWAPOFF, "swapoff", 1),
+    (SYS_MPROTECT, "mprotect", 3),
+    (SYS_MSYNC, "msync", 3),
+    (SYS_MLOCK, "mlock", 2),
+    (SYS_MUNLOCK, "munlock", 2),
+    (SYS_MLOCKALL, "mlockall", 1),
+    (SYS_MUNLOCKALL, "munlockall", 0),
+    (SYS_MINCORE, "mincore", 3),
+    (SYS_MADVISE, "madvise", 3),
+    (SYS_REMAP_FILE_PAGES, "remap_file_pages", 5),
+    (SYS_MBIND, "mbind", 6),
+    (SYS_GET_MEMPOLICY, "get_mempolicy", 5),
+    (SYS_SET_MEMPOLICY, "set_mempolicy", 3),
+    (SYS_MIGRATE_PAGES, "migrate_pages", 4),
+    (SYS_MOVE_PAGES, "move_pages", 6),
+    (SYS_RT_TGSIGQUEUEINFO, "rt_tgsigqueueinfo", 4),
+    (SYS_PERF_EVENT_OPEN, "perf_event_open", 5),
+    (SYS_ACCEPT4, "accept4", 4),
+    (SYS_RECVMMSG, "recvmmsg", 5),
+    (SYS_RISCV_HWPROBE, "riscv_hwprobe", 5),
+    (SYS_RISCV_FLUSH_ICACHE, "riscv_flush_icache", 3),
+    (SYS_WAIT4, "wait4", 4),
+    (SYS_PRLIMIT64, "prlimit64", 4),
+    (SYS_FANOTIFY_INIT, "fanotify_init", 2),
+    (SYS_FANOTIFY_MARK, "fanotify_mark", 5),
+    (SYS_NAME_TO_HANDLE_AT, "name_to_handle_at", 5),
+    (SYS_OPEN_BY_HANDLE_AT, "open_by_handle_at", 3),
+    (SYS_CLOCK_ADJTIME, "clock_adjtime", 2),
+    (SYS_SYNCFS, "syncfs", 1),
+    (SYS_SETNS, "setns", 2),
+    (SYS_SENDMMSG, "sendmmsg", 4),
+    (SYS_PROCESS_VM_READV, "process_vm_readv", 6),
+    (SYS_PROCESS_VM_WRITEV, "process_vm_writev", 6),
+    (SYS_KCMP, "kcmp", 5),
+    (SYS_FINIT_MODULE, "finit_module", 3),
+    (SYS_SCHED_SETATTR, "sched_setattr", 3),
+    (SYS_SCHED_GETATTR, "sched_getattr", 4),
+    (SYS_RENAMEAT2, "renameat2", 5),
+    (SYS_SECCOMP, "seccomp", 3),
+    (SYS_GETRANDOM, "getrandom", 3),
+    (SYS_MEMFD_CREATE, "memfd_create", 2),
+    (SYS_BPF, "bpf", 3),
+    (SYS_EXECVEAT, "execveat", 5),
+    (SYS_USERFAULTFD, "userfaultfd", 1),
+    (SYS_MEMBARRIER, "membarrier", 3),
+    (SYS_MLOCK2, "mlock2", 3),
+    (SYS_COPY_FILE_RANGE, "copy_file_range", 6),
+    (SYS_PREADV2, "preadv2", 6),
+    (SYS_PWRITEV2, "pwritev2", 6),
+    (SYS_PKEY_MPROTECT, "pkey_mprotect", 4),
+    (SYS_PKEY_ALLOC, "pkey_alloc", 2),
+    (SYS_PKEY_FREE, "pkey_free", 1),
+    (SYS_STATX, "statx", 5),
+    (SYS_IO_PGETEVENTS, "io_pgetevents", 6),
+    (SYS_RSEQ, "rseq", 4),
+    (SYS_KEXEC_FILE_LOAD, "kexec_file_load", 5),
+    (SYS_PIDFD_SEND_SIGNAL, "pidfd_send_signal", 4),
+    (SYS_IO_URING_SETUP, "io_uring_setup", 2),
+    (SYS_IO_URING_ENTER, "io_uring_enter", 6),
+    (SYS_IO_URING_REGISTER, "io_uring_register", 4),
+    (SYS_OPEN_TREE, "open_tree", 3),
+    (SYS_MOVE_MOUNT, "move_mount", 5),
+    (SYS_FSOPEN, "fsopen", 2),
+    (SYS_FSCONFIG, "fsconfig", 5),
+    (SYS_FSMOUNT, "fsmount", 3),
+    (SYS_FSPICK, "fspick", 3),
+    (SYS_PIDFD_OPEN, "pidfd_open", 2),
+    (SYS_CLONE3, "clone3", 2),
+    (SYS_CLOSE_RANGE, "close_range", 3),
+    (SYS_OPENAT2, "openat2", 4),
+    (SYS_PIDFD_GETFD, "pidfd_getfd", 3),
+    (SYS_FACCESSAT2, "faccessat2", 4),
+    (SYS_PROCESS_MADVISE, "process_madvise", 5),
+    (SYS_EPOLL_PWAIT2, "epoll_pwait2", 6),
+    (SYS_MOUNT_SETATTR, "mount_setattr", 5),
+    (SYS_QUOTACTL_FD, "quotactl_fd", 4),
+    (SYS_LANDLOCK_CREATE_RULESET, "landlock_create_ruleset", 3),
+    (SYS_LANDLOCK_ADD_RULE, "landlock_add_rule", 4),
+    (SYS_LANDLOCK_RESTRICT_SELF, "landlock_restrict_self", 2),
+    (SYS_MEMFD_SECRET, "memfd_secret", 1),
+    (SYS_PROCESS_MRELEASE, "process_mrelease", 2),
+    (SYS_FUTEX_WAITV, "futex_waitv", 5),
+    (SYS_SET_MEMPOLICY_HOME_NODE, "set_mempolicy_home_node", 4),
+];
+
+/// Number of syscall by its name, if riscv64 has it.
+pub fn syscall_no(name: &str) -> Option<u64> {
+    SYSCALLS.iter().find(|s| s.1 == name).map(|s| s.0)
+}
+
+/// Name and number of arguments of syscall, if riscv64 has it.
+pub fn syscall_info(sysno: u64) -> Option<(&'static str, usize)> {
+    let idx = SYSCALLS.binary_search_by_key(&sysno, |s| s.0).ok()?;
+    Some((SYSCALLS[idx].1, SYSCALLS[idx].2))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::*;
+
+    #[test]
+    fn syscalls_sorted_and_unique() {
+        assert!(SYSCALLS.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(SYSCALLS.iter().all(|s| s.0 < MAX_SYSCALL_NBR));
+        let names: HashSet<_> = SYSCALLS.iter().map(|s| s.1).collect();
+        assert_eq!(names.len(), SYSCALLS.len());
+    }
+
+    #[test]
+    fn lookup_by_number_and_name() {
+        assert_eq!(syscall_info(SYS_OPENAT), Some(("openat", 4)));
+        assert_eq!(syscall_no("openat"), Some(SYS_OPENAT));
+        assert_eq!(syscall_no("open"), None);
+    }
+}
@@ -0,0 +1,511 @@
+//! Tree of processes and threads, rebuilt from clone, execve, wait4,
+//! exit and exit_group.
+//!
+//! A task is cloned by the task whose clone request starts its flow, as
+//! paired by [`crate::level1::Grouper`]. Its pid (or tid) is the result
+//! of that clone in the parent, and it's reaped by the task whose wait4
+//! returns that pid. Lifetimes are in numbers of events in file.
+//!
+//! Threads (CLONE_THREAD) may also be shown under the leader of their
+//! thread group, with processes under the thread group that cloned them.
+
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::fmt::Write;
+use crate::Options;
+use crate::clone::{clone_kind, cloned_by, CLONE_THREAD};
+use crate::errno::is_error;
+use crate::error::Result;
+use crate::filter::Filter;
+use crate::event::{SeqNames, SigStage, TraceEvent, TraceFlow};
+use crate::level1;
+use crate::sysno::*;
+
+/// Task in process tree, from its first event to its exit. The kernel
+/// reuses sscratch of tasks which exited, so a sscratch may have
+/// several nodes. Nodes refer to each other by index in tree.
+#[derive(Clone, Debug, Default)]
+pub struct TaskNode {
+    pub tid: u64,
+    /// Number of earlier tasks of the same sscratch
+    pub reuse: usize,
+    /// Node of task which cloned this one
+    pub parent: Option<usize>,
+    /// Pid (or tid) given by clone, gettid or getpid
+    pub pid: Option<i64>,
+    /// Programs executed, in order
+    pub programs: Vec<String>,
+    /// Number of first event of task
+    pub start: u64,
+    /// Number of last event of task
+    pub end: u64,
+    /// Status of exit or exit_group
+    pub exit_code: Option<i64>,
+    /// Node of task whose wait4 reaped this one, and number of its reply
+    pub reaped_by: Option<(usize, u64)>,
+    /// Flags of clone which created this task
+    pub clone_flags: Option<u64>,
+    /// Node of leader of thread group
+    pub leader: usize,
+    /// Address spaces (satp) in order, which change by execve
+    pub spaces: Vec<u64>,
+    /// Nodes of children
+    pub children: Vec<usize>,
+    /// Task which cloned this one, and number of its clone request
+    clone: Option<(u64, u64)>,
+}
+
+/// Tree of tasks, fed with flows of tasks.
+#[derive(Default)]
+pub struct ProcessTree {
+    nodes: Vec<TaskNode>,
+    /// Node of the last task of each sscratch
+    live: HashMap<u64, usize>,
+    /// Nodes in order of first event
+    order: Vec<usize>,
+    /// Pids reaped by wait4: (node of reaper, pid, number of reply)
+    reaped: Vec<(usize, i64, u64)>,
+    /// Results of clone in parents by number of request, which is also
+    /// the first event of child
+    clone_pids: HashMap<u64, i64>,
+}
+
+fn c_str(evt: &TraceEvent, index: usize) -> Option<String> {
+    let payload = evt.payload(index)?;
+    let s = CStr::from_bytes_until_nul(&payload.data).ok()?;
+    Some(s.to_string_lossy().into_owned())
+}
+
+/// Last of nodes, in order of first event, which started by seq.
+fn started_by(nodes: &[TaskNode], candidates: Option<&Vec<usize>>, seq: u64) -> Option<usize> {
+    candidates?.iter().rev().copied().find(|idx| nodes[*idx].start <= seq)
+}
+
+impl ProcessTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Node of task, which is added for the first flow of task. In
+    /// streaming mode, a task has several flows.
+    fn node(&mut self, tid: u64, flow: &TraceFlow, start: u64) -> usize {
+        match self.live.get(&tid) {
+            Some(idx) if flow.flushed > 0 => *idx,
+            live => {
+                let reuse = live.map_or(0, |idx| self.nodes[*idx].reuse + 1);
+                self.nodes.push(TaskNode { tid, reuse, start, end: start, ..Default::default() });
+                self.live.insert(tid, self.nodes.len() - 1);
+                self.nodes.len() - 1
+            },
+        }
+    }
+
+    /// Add events of task.
+    pub fn add(&mut self, tid: u64, flow: &TraceFlow) {
+        let Some(first) = flow.events.first() else {
+            return;
+        };
+        let idx = self.node(tid, flow, first.seq);
+        let node = &mut self.nodes[idx];
+        // A new task starts with a copy of its parent's clone request,
+        // whose satp is the parent's
+        let parent = cloned_by(tid, flow);
+        if let Some(parent) = parent {
+            node.clone = Some((parent, first.seq));
+            node.clone_flags = Some(first.head.ax[0]);
+        }
+        for (i, evt) in flow.events.iter().enumerate() {
+            node.end = node.end.max(evt.reply_seq.unwrap_or(evt.seq));
+            if let SigStage::Enter(_) = evt.signal {
+                continue;
+            }
+            if (i > 0 || parent.is_none()) && node.spaces.last() != Some(&evt.head.satp) {
+                node.spaces.push(evt.head.satp);
+            }
+            let ok = !is_error(evt.result) && !matches!(evt.signal, SigStage::Exit(_));
+            match evt.head.ax[7] {
+                // Clone in the child returns 0
+                SYS_CLONE if ok && evt.result > 0 => {
+                    self.clone_pids.insert(evt.seq, evt.result);
+                },
+                SYS_EXECVE if evt.result == 0 => node.programs.extend(c_str(evt, 0)),
+                SYS_GETTID if ok => {
+                    node.pid = Some(evt.result);
+                },
+                SYS_GETPID if ok => {
+                    node.pid.get_or_insert(evt.result);
+                },
+                SYS_WAIT4 if ok && evt.result > 0 => {
+                    self.reaped.push((idx, evt.result, evt.reply_seq.unwrap_or(evt.seq)));
+                },
+                SYS_EXIT | SYS_EXIT_GROUP => node.exit_code = Some(evt.head.ax[0] as i64),
+                _ => (),
+            }
+        }
+    }
+
+    /// Resolve parents, pids of children and reaping by wait4, after
+    /// all flows are added. Flows of children come before their
+    /// parents', which hold the results of clone. The parent of a task
+    /// is the last task of its sscratch that started by the clone, and
+    /// a parent which isn't in trace is added without events.
+    pub fn finish(&mut self) {
+        self.sort_order();
+        let mut by_tid: HashMap<u64, Vec<usize>> = HashMap::new();
+        for idx in &self.order {
+            by_tid.entry(self.nodes[*idx].tid).or_default().push(*idx);
+        }
+        for idx in self.order.clone() {
+            let Some((parent, seq)) = self.nodes[idx].clone else {
+                continue;
+            };
+            let parent = match started_by(&self.nodes, by_tid.get(&parent), seq) {
+                Some(parent) => parent,
+                None => {
+                    // Tasks of its sscratch all start later
+                    self.nodes.push(TaskNode { tid: parent, start: seq, end: seq, ..Default::default() });
+                    by_tid.entry(parent).or_default().insert(0, self.nodes.len() - 1);
+                    self.nodes.len() - 1
+                },
+            };
+            self.nodes[idx].parent = Some(parent);
+            self.nodes[parent].children.push(idx);
+            if let Some(pid) = self.clone_pids.get(&seq) {
+                self.nodes[idx].pid = Some(*pid);
+            }
+        }
+        // Parents without events go before their first child
+        self.sort_order();
+        for idx in 0..self.nodes.len() {
+            self.nodes[idx].leader = self.leader(idx);
+        }
+
+        let mut pids: HashMap<i64, Vec<usize>> = HashMap::new();
+        for idx in &self.order {
+            if let Some(pid) = self.nodes[*idx].pid {
+                pids.entry(pid).or_default().push(*idx);
+            }
+        }
+        for (reaper, pid, seq) in std::mem::take(&mut self.reaped) {
+            if let Some(idx) = started_by(&self.nodes, pids.get(&pid), seq) {
+                self.nodes[idx].reaped_by = Some((reaper, seq));
+            }
+        }
+    }
+
+    /// Order nodes by first event. A cloned task starts with its
+    /// parent's clone request, so it goes after a parent which starts
+    /// with it.
+    fn sort_order(&mut self) {
+        self.order = (0..self.nodes.len()).collect();
+        self.order.sort_by_key(|idx| (self.nodes[*idx].start, self.nodes[*idx].clone.is_some()));
+    }
+
+    /// Leader of thread group of node, i.e. the first task of its
+    /// process in trace.
+    fn leader(&self, idx: usize) -> usize {
+        let mut leader = idx;
+        // Bounded, in case of a damaged trace
+        for _ in 0..self.nodes.len() {
+            let node = &self.nodes[leader];
+            match (node.parent, node.clone_flags) {
+                (Some(parent), Some(flags)) if flags & CLONE_THREAD != 0 => leader = parent,
+                _ => break,
+            }
+        }
+        leader
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = &TaskNode> {
+        self.order.iter().map(|idx| &self.nodes[*idx])
+    }
+
+    /// Tasks without parent in trace, in order of first event.
+    pub fn roots(&self) -> impl Iterator<Item = &TaskNode> {
+        self.nodes().filter(|n| n.parent.is_none())
+    }
+
+    /// Node under which a node is shown. With threads, a thread is
+    /// under the leader of its group, and a process under the leader of
+    /// the group which cloned it.
+    fn shown_parent(&self, node: &TaskNode, threads: bool) -> Option<usize> {
+        let parent = node.parent?;
+        if !threads {
+            Some(parent)
+        } else if node.clone_flags.is_some_and(|flags| flags & CLONE_THREAD != 0) {
+            Some(node.leader)
+        } else {
+            Some(self.nodes[parent].leader)
+        }
+    }
+
+    /// Roots and children of nodes as shown, in order of first event.
+    fn layout(&self, threads: bool) -> (Vec<usize>, HashMap<usize, Vec<usize>>) {
+        let mut roots = vec![];
+        let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+        for idx in &self.order {
+            match self.shown_parent(&self.nodes[*idx], threads) {
+                Some(parent) => children.entry(parent).or_default().push(*idx),
+                None => roots.push(*idx),
+            }
+        }
+        (roots, children)
+    }
+
+    /// Name of task, and the number of its sscratch if it's reused.
+    fn name(&self, idx: usize, names: &SeqNames) -> String {
+        let node = &self.nodes[idx];
+        match node.reuse {
+            0 => names.task(node.tid),
+            reuse => format!("{}#{}", names.task(node.tid), reuse),
+        }
+    }
+
+    fn label(&self, idx: usize, names: &SeqNames, threads: bool, sep: &str) -> String {
+        let node = &self.nodes[idx];
+        let mut label = self.name(idx, names);
+        if let Some(pid) = node.pid {
+            let pid = names.pid(pid).unwrap_or_else(|| pid.to_string());
+            let _ = write!(label, "{}pid {}", sep, pid);
+        }
+        match node.clone_flags.map(clone_kind) {
+            Some("process") | None => (),
+            Some(kind) => { let _ = write!(label, "{}{}", sep, kind); },
+        }
+        for program in &node.programs {
+            let _ = write!(label, "{}{}", sep, program);
+        }
+        let _ = write!(label, "{}[{}..{}]", sep, node.start, node.end);
+        if let Some(code) = node.exit_code {
+            let _ = write!(label, "{}exit {}", sep, code);
+        }
+        if let Some((reaper, seq)) = node.reaped_by {
+            let _ = write!(label, "{}reaped by {} at {}", sep, self.name(reaper, names), seq);
+        }
+        if threads && !node.spaces.is_empty() {
+            let spaces = node.spaces.iter().map(|satp| format!("{:#x}", satp)).collect::<Vec<_>>();
+            let _ = write!(label, "{}satp {}", sep, spaces.join(" -> "));
+        }
+        label
+    }
+
+    /// Write children of node, each once, as a damaged trace may have a
+    /// cycle of parents.
+    fn write_subtree(
+        out: &mut String, children: &HashMap<usize, Vec<usize>>, label: &dyn Fn(usize) -> String,
+        visited: &mut [bool], idx: usize, prefix: &str,
+    ) {
+        let kids = children.get(&idx).map_or(&[][..], |v| v.as_slice());
+        let kids = kids.iter().filter(|kid| !visited[**kid]).copied().collect::<Vec<_>>();
+        kids.iter().for_each(|kid| visited[*kid] = true);
+        for (i, kid) in kids.iter().enumerate() {
+            let last = i + 1 == kids.len();
+            let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+            let _ = writeln!(out, "{}{}{}", prefix, branch, label(*kid));
+            let prefix = format!("{}{}", prefix, indent);
+            Self::write_subtree(out, children, label, visited, *kid, &prefix);
+        }
+    }
+
+    /// Format tree like pstree, one task per line. With threads, tasks
+    /// are grouped by thread group, see [`Self::shown_parent`]. Tasks
+    /// in a cycle of parents are shown as roots.
+    pub fn to_text(&self, names: &SeqNames, threads: bool) -> String {
+        let mut out = String::new();
+        let (roots, children) = self.layout(threads);
+        let label = |idx| self.label(idx, names, threads, " ");
+        let mut visited = vec![false; self.nodes.len()];
+        let cycles = self.order.iter().copied().filter(|idx| !roots.contains(idx));
+        for root in roots.iter().copied().chain(cycles).collect::<Vec<_>>() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            let _ = writeln!(out, "{}", label(root));
+            Self::write_subtree(&mut out, &children, &label, &mut visited, root, "");
+        }
+        out
+    }
+
+    /// Thread groups, each with its nodes, in order of first event.
+    fn groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = vec![];
+        let mut index = HashMap::new();
+        for idx in &self.order {
+            let group = *index.entry(self.nodes[*idx].leader).or_insert_with(|| {
+                groups.push(vec![]);
+                groups.len() - 1
+            });
+            groups[group].push(*idx);
+        }
+        groups
+    }
+
+    /// Format tree as Graphviz DOT. Clone edges are solid, and reaping
+    /// by wait4 is dashed. With threads, each thread group is a cluster.
+    pub fn to_dot(&self, names: &SeqNames, threads: bool) -> String {
+        let mut out = String::from("digraph tasks {\n    node [shape=box];\n");
+        let node_line = |out: &mut String, idx: usize, indent: &str| {
+            let label = self.label(idx, names, threads, "\\n").replace('"', "\\\"");
+            let _ = writeln!(out, "{}\"{}\" [label=\"{}\"];", indent, self.name(idx, names), label);
+        };
+        if threads {
+            for (i, group) in self.groups().iter().enumerate() {
+                let _ = writeln!(out, "    subgraph cluster_{} {{", i);
+                group.iter().for_each(|idx| node_line(&mut out, *idx, "        "));
+                out.push_str("    }\n");
+            }
+        } else {
+            self.order.iter().for_each(|idx| node_line(&mut out, *idx, "    "));
+        }
+        let (_, children) = self.layout(threads);
+        for idx in &self.order {
+            for child in children.get(idx).into_iter().flatten() {
+                let _ = writeln!(out, "    \"{}\" -> \"{}\";",
+                    self.name(*idx, names), self.name(*child, names));
+            }
+            if let Some((reaper, _)) = self.nodes[*idx].reaped_by {
+                let _ = writeln!(out, "    \"{}\" -> \"{}\" [style=dashed, label=\"wait4\"];",
+                    self.name(reaper, names), self.name(*idx, names));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Build process tree of trace data file, and print it as text or DOT.
+/// With threads, tasks are grouped by thread group. The tree is built
+/// from all events of selected tasks, as filters of syscalls would
+/// leave out clone or exit_group.
+pub fn print(path: &str, opts: &Options, dot: bool, threads: bool) -> Result<()> {
+    let mut tree = ProcessTree::new();
+    let names = SeqNames::new(opts.level);
+    let opts = Options { filter: Filter::default(), ..opts.clone() };
+    level1::group(path, &opts, &names, |tid, flow| {
+        tree.add(tid, &flow);
+        Ok(())
+    })?;
+    tree.finish();
+    if dot {
+        print!("{}", tree.to_dot(&names, threads));
+    } else {
+        print!("{}", tree.to_text(&names, threads));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::USER_ECALL;
+
+    const PARENT: u64 = 0x1000;
+    const CHILD: u64 = 0x2000;
+
+    /// Completed syscall of task at seq, with its first argument and
+    /// result.
+    fn event(tid: u64, seq: u64, sysno: u64, arg: u64, result: i64) -> TraceEvent {
+        let mut evt = TraceEvent { level: 1, seq, result, ..Default::default() };
+        evt.head.cause = USER_ECALL;
+        evt.head.ax[0] = arg;
+        evt.head.ax[7] = sysno;
+        evt.head.sscratch = tid;
+        evt
+    }
+
+    fn flow(events: Vec<TraceEvent>) -> TraceFlow {
+        TraceFlow { events, ..TraceFlow::new() }
+    }
+
+    #[test]
+    fn reused_sscratch_splits() {
+        // Parent forks a child and reaps it, then clones a thread on the
+        // sscratch of child. Flows come as tasks exit
+        let fork = event(PARENT, 0, SYS_CLONE, 17, 0);
+        let thread = event(PARENT, 5, SYS_CLONE, 0x10f00, 0);
+        let mut tree = ProcessTree::new();
+        tree.add(CHILD, &flow(vec![fork, event(CHILD, 2, SYS_EXIT_GROUP, 1, 0)]));
+        tree.add(CHILD, &flow(vec![thread, event(CHILD, 7, SYS_EXIT, 3, 0)]));
+        tree.add(PARENT, &flow(vec![
+            event(PARENT, 0, SYS_CLONE, 17, 5),
+            event(PARENT, 3, SYS_WAIT4, -1i64 as u64, 5),
+            event(PARENT, 5, SYS_CLONE, 0x10f00, 6),
+            event(PARENT, 8, SYS_EXIT_GROUP, 0, 0),
+        ]));
+        tree.finish();
+
+        let text = tree.to_text(&SeqNames::new(1), false);
+        assert_eq!(text, "0x1000 [0..8] exit 0\n\
+            ├── 0x2000 pid 5 [0..2] exit 1 reaped by 0x1000 at 3\n\
+            └── 0x2000#1 pid 6 thread [5..7] exit 3\n");
+    }
+
+    #[test]
+    fn cycle_is_shown_once() {
+        // A damaged trace may make a task its own ancestor
+        let node = |tid, parent| TaskNode { tid, parent: Some(parent), ..Default::default() };
+        let mut tree = ProcessTree::new();
+        tree.nodes = vec![node(1, 1), node(2, 0), node(3, 2), node(4, 2)];
+        tree.order = (0..4).collect();
+        let text = tree.to_text(&SeqNames::new(1), false);
+        assert_eq!(text, "0x1 [0..0]\n└── 0x2 [0..0]\n0x3 [0..0]\n└── 0x4 [0..0]\n");
+        assert_eq!(tree.to_dot(&SeqNames::new(1), false).matches(" -> ").count(), 4);
+    }
+
+    #[test]
+    fn dot_clusters_thread_groups() {
+        // Parent clones a thread, which forks a child and clones another
+        // thread. Parent reaps the child
+        const THREAD: u64 = 0x3000;
+        const THREAD2: u64 = 0x4000;
+        let mut tree = ProcessTree::new();
+        tree.add(CHILD, &flow(vec![
+            event(THREAD, 2, SYS_CLONE, 17, 0),
+            event(CHILD, 4, SYS_EXIT_GROUP, 0, 0),
+        ]));
+        tree.add(THREAD2, &flow(vec![
+            event(THREAD, 5, SYS_CLONE, 0x10f00, 0),
+            event(THREAD2, 6, SYS_EXIT, 0, 0),
+        ]));
+        tree.add(THREAD, &flow(vec![
+            event(PARENT, 0, SYS_CLONE, 0x10f00, 0),
+            event(THREAD, 2, SYS_CLONE, 17, 30),
+            event(THREAD, 5, SYS_CLONE, 0x10f00, 31),
+            event(THREAD, 7, SYS_EXIT, 0, 0),
+        ]));
+        tree.add(PARENT, &flow(vec![
+            event(PARENT, 0, SYS_CLONE, 0x10f00, 20),
+            event(PARENT, 8, SYS_WAIT4, -1i64 as u64, 30),
+            event(PARENT, 9, SYS_EXIT_GROUP, 0, 0),
+        ]));
+        tree.finish();
+
+        // Child is under the thread which cloned it, or under the leader
+        // of its group with threads
+        let child = tree.nodes().find(|node| node.tid == CHILD).unwrap();
+        let shown = |threads| tree.nodes[tree.shown_parent(child, threads).unwrap()].tid;
+        assert_eq!((shown(false), shown(true)), (THREAD, PARENT));
+
+        let names = SeqNames::new(1);
+        assert_eq!(tree.to_dot(&names, true), r#"digraph tasks {
+    node [shape=box];
+    subgraph cluster_0 {
+        "0x1000" [label="0x1000\n[0..9]\nexit 0\nsatp 0x0"];
+        "0x3000" [label="0x3000\npid 20\nthread\n[0..7]\nexit 0\nsatp 0x0"];
+        "0x4000" [label="0x4000\npid 31\nthread\n[5..6]\nexit 0\nsatp 0x0"];
+    }
+    subgraph cluster_1 {
+        "0x2000" [label="0x2000\npid 30\n[2..4]\nexit 0\nreaped by 0x1000 at 8\nsatp 0x0"];
+    }
+    "0x1000" -> "0x3000";
+    "0x1000" -> "0x2000";
+    "0x1000" -> "0x4000";
+    "0x1000" -> "0x2000" [style=dashed, label="wait4"];
+}
+"#);
+        let dot = tree.to_dot(&names, false);
+        assert!(!dot.contains("subgraph"));
+        assert!(dot.contains("\"0x3000\" -> \"0x2000\";\n    \"0x3000\" -> \"0x4000\";\n"), "{}", dot);
+    }
+}